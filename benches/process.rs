@@ -0,0 +1,81 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+
+use note_sequencer::engine::TransportSnapshot;
+use note_sequencer::MyPlugin;
+
+const SAMPLE_RATE: f32 = 48000.0;
+const TEMPO: f64 = 120.0;
+
+// the plugin's process path with the transport running, playing a note
+// on every step of the given number of tracks
+struct Sequencer {
+    plugin: MyPlugin,
+    pos_samples: i64,
+}
+
+impl Sequencer {
+    fn new(steps_per_beat: u32, tracks: usize) -> Self {
+        let mut plugin = MyPlugin::default();
+        plugin.bench_pattern(steps_per_beat, tracks);
+        Self {
+            plugin,
+            pos_samples: 0,
+        }
+    }
+
+    // process one buffer, returning the number of events sent
+    fn process(&mut self, buffer_samples: usize) -> usize {
+        let samples_per_beat = SAMPLE_RATE as f64 * 60.0 / TEMPO;
+        let snapshot = TransportSnapshot {
+            pos_beats: self.pos_samples as f64 / samples_per_beat,
+            pos_samples: Some(self.pos_samples),
+            tempo: TEMPO,
+            bar_beats: 4.0,
+            bar_number: None,
+            bar_start_beats: None,
+        };
+
+        let mut sent = 0;
+        self.plugin
+            .bench_buffer(&snapshot, buffer_samples, SAMPLE_RATE, |event| {
+                black_box(event);
+                sent += 1;
+            });
+        self.pos_samples += buffer_samples as i64;
+        sent
+    }
+}
+
+// each iteration is one buffer, the song keeps playing from one iteration to the next
+fn bench_buffer(c: &mut Criterion, group: &str, id: BenchmarkId, setup: (usize, u32, usize)) {
+    let (buffer_samples, steps_per_beat, tracks) = setup;
+    let mut sequencer = Sequencer::new(steps_per_beat, tracks);
+    c.benchmark_group(group).bench_function(id, |b| {
+        b.iter(|| sequencer.process(black_box(buffer_samples)))
+    });
+}
+
+fn buffer_sizes(c: &mut Criterion) {
+    for buffer_samples in [32, 64, 128, 256, 512, 1024, 2048, 4096] {
+        let id = BenchmarkId::from_parameter(buffer_samples);
+        bench_buffer(c, "buffer_size", id, (buffer_samples, 4, 4));
+    }
+}
+
+fn step_lengths(c: &mut Criterion) {
+    for steps_per_beat in [1, 2, 4, 8] {
+        let id = BenchmarkId::from_parameter(steps_per_beat);
+        bench_buffer(c, "steps_per_beat", id, (512, steps_per_beat, 4));
+    }
+}
+
+fn track_counts(c: &mut Criterion) {
+    for tracks in 1..=4 {
+        let id = BenchmarkId::from_parameter(tracks);
+        bench_buffer(c, "tracks", id, (512, 4, tracks));
+    }
+}
+
+criterion_group!(benches, buffer_sizes, step_lengths, track_counts);
+criterion_main!(benches);
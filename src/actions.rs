@@ -0,0 +1,252 @@
+use nih_plug::prelude::{
+    formatters, BoolParam, FloatParam, FloatRange, IntParam, IntRange, Params,
+};
+
+use crate::pattern::{Pattern, Track, NUM_PATTERNS};
+use crate::trigger::Trigger;
+
+#[derive(Params)]
+pub struct ActionParams {
+    // slot the selected pattern is copied to
+    #[id = "copy_to"]
+    copy_to: IntParam,
+
+    #[id = "copy"]
+    copy: BoolParam,
+
+    // copy the selected pattern to the first empty slot
+    #[id = "duplicate"]
+    duplicate: BoolParam,
+
+    #[id = "clear"]
+    clear: BoolParam,
+
+    // restore the slot changed by the last action, undoing again redoes it
+    #[id = "undo"]
+    undo: BoolParam,
+
+    // write the selected pattern to a midi file in the export folder
+    #[id = "export_midi"]
+    export_midi: BoolParam,
+
+    // send the selected pattern out as sysex, for hardware sequencers to take in
+    #[id = "dump_sysex"]
+    dump_sysex: BoolParam,
+
+    // give the selected pattern a new random seed, for new variations of its random choices
+    #[id = "reseed"]
+    reseed: BoolParam,
+
+    // fill the edited track with random notes of the scale
+    #[id = "randomize"]
+    randomize: BoolParam,
+
+    // chance of each randomized step playing
+    #[id = "random_density"]
+    pub random_density: FloatParam,
+
+    // octaves the randomized notes are spread over
+    #[id = "random_octaves"]
+    pub random_octaves: IntParam,
+
+    // make small random edits to the edited track, triggered again and again to evolve it
+    #[id = "mutate"]
+    mutate: BoolParam,
+
+    // chance of each step being edited by a mutation
+    #[id = "mutate_amount"]
+    pub mutate_amount: FloatParam,
+}
+
+impl Default for ActionParams {
+    fn default() -> Self {
+        Self {
+            copy_to: IntParam::new(
+                "Copy To",
+                2,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_PATTERNS as i32,
+                },
+            ),
+            copy: BoolParam::new("Copy", false),
+            duplicate: BoolParam::new("Duplicate", false),
+            clear: BoolParam::new("Clear", false),
+            undo: BoolParam::new("Undo", false),
+            export_midi: BoolParam::new("Export MIDI", false),
+            dump_sysex: BoolParam::new("SysEx Dump", false),
+            reseed: BoolParam::new("Reseed", false),
+            randomize: BoolParam::new("Randomize", false),
+            random_density: FloatParam::new(
+                "Random Density",
+                0.75,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            random_octaves: IntParam::new("Random Octaves", 1, IntRange::Linear { min: 1, max: 4 }),
+            mutate: BoolParam::new("Mutate", false),
+            mutate_amount: FloatParam::new(
+                "Mutate Amount",
+                0.1,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+// copy, duplicate and clear whole pattern slots
+#[derive(Default)]
+pub struct PatternActions {
+    copy: Trigger,
+    duplicate: Trigger,
+    clear: Trigger,
+    undo: Trigger,
+    export_midi: Trigger,
+    dump_sysex: Trigger,
+    reseed: Trigger,
+    randomize: Trigger,
+    mutate: Trigger,
+
+    // slot changed by the last action along with what it held before
+    undo_state: Option<(usize, Pattern)>,
+}
+
+impl PatternActions {
+    // returns true if a pattern slot was changed
+    pub fn run(
+        &mut self,
+        params: &ActionParams,
+        patterns: &mut [Pattern; NUM_PATTERNS],
+        selected_pattern: usize,
+    ) -> bool {
+        let mut changed = false;
+
+        if self.copy.fired(params.copy.value()) {
+            let target = params.copy_to.value() as usize - 1;
+            if target != selected_pattern {
+                nih_log!("copy pattern {} to {}", selected_pattern + 1, target + 1);
+                let pattern = patterns[selected_pattern].clone();
+                self.replace(patterns, target, pattern);
+                changed = true;
+            }
+        }
+
+        if self.duplicate.fired(params.duplicate.value()) {
+            let target = (0..NUM_PATTERNS)
+                .find(|&index| index != selected_pattern && patterns[index].is_empty());
+            match target {
+                Some(target) => {
+                    nih_log!(
+                        "duplicate pattern {} to {}",
+                        selected_pattern + 1,
+                        target + 1
+                    );
+                    let pattern = patterns[selected_pattern].clone();
+                    self.replace(patterns, target, pattern);
+                    changed = true;
+                }
+                None => {
+                    nih_log!("no empty pattern to duplicate to");
+                }
+            }
+        }
+
+        if self.clear.fired(params.clear.value()) {
+            nih_log!("clear pattern {}", selected_pattern + 1);
+            self.replace(patterns, selected_pattern, Pattern::default());
+            changed = true;
+        }
+
+        if self.undo.fired(params.undo.value()) {
+            match self.undo_state.take() {
+                Some((index, pattern)) => {
+                    nih_log!("undo pattern {}", index + 1);
+                    self.replace(patterns, index, pattern);
+                    changed = true;
+                }
+                None => {
+                    nih_log!("nothing to undo");
+                }
+            }
+        }
+
+        changed
+    }
+
+    // returns true if the selected pattern should be exported, the file is
+    // written on a background thread
+    pub fn export_midi(&mut self, params: &ActionParams) -> bool {
+        self.export_midi.fired(params.export_midi.value())
+    }
+
+    // returns true if the selected pattern should be sent out as sysex
+    pub fn dump_sysex(&mut self, params: &ActionParams) -> bool {
+        self.dump_sysex.fired(params.dump_sysex.value())
+    }
+
+    // returns true if the selected pattern should be given a new seed
+    pub fn reseed(&mut self, params: &ActionParams) -> bool {
+        self.reseed.fired(params.reseed.value())
+    }
+
+    // returns true if the edited track should be randomized
+    pub fn randomize(&mut self, params: &ActionParams) -> bool {
+        self.randomize.fired(params.randomize.value())
+    }
+
+    // returns true if the edited track should be mutated
+    pub fn mutate(&mut self, params: &ActionParams) -> bool {
+        self.mutate.fired(params.mutate.value())
+    }
+
+    // replace a single track of a slot, undoable like the other actions
+    pub fn replace_track(
+        &mut self,
+        patterns: &mut [Pattern; NUM_PATTERNS],
+        index: usize,
+        track_index: usize,
+        track: Track,
+    ) {
+        let mut pattern = patterns[index].clone();
+        pattern.tracks[track_index] = track;
+        self.replace(patterns, index, pattern);
+    }
+
+    // change the seed of a slot, undoable like the other actions
+    pub fn set_seed(&mut self, patterns: &mut [Pattern; NUM_PATTERNS], index: usize, seed: u32) {
+        let pattern = Pattern {
+            seed,
+            ..patterns[index].clone()
+        };
+        self.replace(patterns, index, pattern);
+    }
+
+    // put a pattern from outside the plugin into a slot, undoable like the other actions
+    pub fn load(&mut self, patterns: &mut [Pattern; NUM_PATTERNS], index: usize, pattern: Pattern) {
+        nih_log!("load pattern {}", index + 1);
+        self.replace(patterns, index, pattern);
+    }
+
+    // take on the current parameter values without running any actions
+    pub fn sync(&mut self, params: &ActionParams) {
+        self.copy.sync(params.copy.value());
+        self.duplicate.sync(params.duplicate.value());
+        self.clear.sync(params.clear.value());
+        self.undo.sync(params.undo.value());
+        self.export_midi.sync(params.export_midi.value());
+        self.dump_sysex.sync(params.dump_sysex.value());
+        self.reseed.sync(params.reseed.value());
+        self.randomize.sync(params.randomize.value());
+        self.mutate.sync(params.mutate.value());
+    }
+
+    fn replace(&mut self, patterns: &mut [Pattern; NUM_PATTERNS], index: usize, pattern: Pattern) {
+        let previous = std::mem::replace(&mut patterns[index], pattern);
+        self.undo_state = Some((index, previous));
+    }
+}
@@ -0,0 +1,130 @@
+use nih_plug::prelude::*;
+
+// maximum number of keys held at once that the arpeggiator keeps track of
+const MAX_KEYS: usize = 16;
+
+// order the held keys are played in
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum ArpMode {
+    #[id = "off"]
+    #[name = "Off"]
+    Off,
+
+    #[id = "up"]
+    #[name = "Up"]
+    Up,
+
+    #[id = "down"]
+    #[name = "Down"]
+    Down,
+
+    #[id = "up_down"]
+    #[name = "Up Down"]
+    UpDown,
+
+    #[id = "played"]
+    #[name = "As Played"]
+    Played,
+
+    #[id = "random"]
+    #[name = "Random"]
+    Random,
+}
+
+// notes held down on the midi input, along with the last chord played
+// which stays latched after its keys are released
+#[derive(Default)]
+pub struct HeldKeys {
+    held: KeyList,
+    latched: KeyList,
+}
+
+impl HeldKeys {
+    pub fn press(&mut self, note: u8) {
+        // a key pressed after all keys were released starts a new chord
+        if self.held.len == 0 {
+            self.latched.clear();
+        }
+        self.held.press(note);
+        self.latched.press(note);
+    }
+
+    pub fn release(&mut self, note: u8) {
+        self.held.release(note);
+    }
+
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.latched.clear();
+    }
+
+    // keys the arpeggiator and transposition act on
+    fn pool(&self, latch: bool) -> &KeyList {
+        if latch {
+            &self.latched
+        } else {
+            &self.held
+        }
+    }
+
+    // most recently pressed key of the pool
+    pub fn last(&self, latch: bool) -> Option<u8> {
+        let pool = self.pool(latch);
+        pool.keys[..pool.len].last().copied()
+    }
+
+    pub fn arp_note(&self, mode: ArpMode, position: usize, latch: bool) -> Option<u8> {
+        self.pool(latch).arp_note(mode, position)
+    }
+}
+
+// keys in the order they were pressed
+#[derive(Default)]
+struct KeyList {
+    keys: [u8; MAX_KEYS],
+    len: usize,
+}
+
+impl KeyList {
+    fn press(&mut self, note: u8) {
+        if self.len == MAX_KEYS || self.keys[..self.len].contains(&note) {
+            return;
+        }
+        self.keys[self.len] = note;
+        self.len += 1;
+    }
+
+    fn release(&mut self, note: u8) {
+        if let Some(index) = self.keys[..self.len].iter().position(|&key| key == note) {
+            self.keys.copy_within(index + 1..self.len, index);
+            self.len -= 1;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // note at the given position of the arpeggio, positions loop around the held keys
+    fn arp_note(&self, mode: ArpMode, position: usize) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let mut sorted = self.keys;
+        sorted[..self.len].sort_unstable();
+
+        let note = match mode {
+            ArpMode::Up => sorted[position % self.len],
+            ArpMode::Down => sorted[self.len - 1 - position % self.len],
+            ArpMode::UpDown => {
+                // the top and bottom keys aren't repeated at the turns
+                let period = (2 * self.len - 2).max(1);
+                let index = position % period;
+                sorted[index.min(period - index)]
+            }
+            ArpMode::Off | ArpMode::Played | ArpMode::Random => self.keys[position % self.len],
+        };
+        Some(note)
+    }
+}
@@ -0,0 +1,31 @@
+// number of entries in the pattern chain
+pub const CHAIN_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainEntry {
+    // pattern slot played by this entry, none ends the chain
+    pub pattern: Option<usize>,
+
+    // number of bars the pattern plays for
+    pub bars: u32,
+}
+
+// pattern played at the given bar of the song, looping over the chain
+pub fn pattern_at_bar(chain: &[ChainEntry], bar: i64) -> Option<usize> {
+    let entries = chain.iter().take_while(|entry| entry.pattern.is_some());
+
+    let chain_bars: i64 = entries.clone().map(|entry| entry.bars as i64).sum();
+    if chain_bars == 0 {
+        return None;
+    }
+
+    let mut bar = bar.rem_euclid(chain_bars);
+    for entry in entries {
+        if bar < entry.bars as i64 {
+            return entry.pattern;
+        }
+        bar -= entry.bars as i64;
+    }
+
+    None
+}
@@ -0,0 +1,64 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// chord built on a step's note
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ChordType {
+    #[id = "none"]
+    #[name = "None"]
+    None,
+
+    #[id = "maj"]
+    #[name = "Major"]
+    Major,
+
+    #[id = "min"]
+    #[name = "Minor"]
+    Minor,
+
+    #[id = "7"]
+    #[name = "7"]
+    Seventh,
+
+    #[id = "maj7"]
+    #[name = "Major 7"]
+    MajorSeventh,
+
+    #[id = "min7"]
+    #[name = "Minor 7"]
+    MinorSeventh,
+
+    #[id = "sus2"]
+    #[name = "Sus2"]
+    Sus2,
+
+    #[id = "sus4"]
+    #[name = "Sus4"]
+    Sus4,
+
+    #[id = "dim"]
+    #[name = "Diminished"]
+    Diminished,
+
+    #[id = "aug"]
+    #[name = "Augmented"]
+    Augmented,
+}
+
+impl ChordType {
+    // semitones above the root of the notes added to it
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            ChordType::None => &[],
+            ChordType::Major => &[4, 7],
+            ChordType::Minor => &[3, 7],
+            ChordType::Seventh => &[4, 7, 10],
+            ChordType::MajorSeventh => &[4, 7, 11],
+            ChordType::MinorSeventh => &[3, 7, 10],
+            ChordType::Sus2 => &[2, 7],
+            ChordType::Sus4 => &[5, 7],
+            ChordType::Diminished => &[3, 6],
+            ChordType::Augmented => &[4, 8],
+        }
+    }
+}
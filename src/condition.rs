@@ -0,0 +1,145 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// condition a step has to meet to play, checked against how many times
+// the track has looped and whether a fill is being played.
+// a:b plays the step on the a-th of every b loops of the track.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrigCondition {
+    #[id = "always"]
+    #[name = "Always"]
+    Always,
+
+    #[id = "fill"]
+    #[name = "Fill"]
+    Fill,
+
+    #[id = "not_fill"]
+    #[name = "Not Fill"]
+    NotFill,
+
+    #[id = "first"]
+    #[name = "First"]
+    First,
+
+    #[id = "not_first"]
+    #[name = "Not First"]
+    NotFirst,
+
+    #[id = "1:2"]
+    #[name = "1:2"]
+    OneOfTwo,
+
+    #[id = "2:2"]
+    #[name = "2:2"]
+    TwoOfTwo,
+
+    #[id = "1:3"]
+    #[name = "1:3"]
+    OneOfThree,
+
+    #[id = "2:3"]
+    #[name = "2:3"]
+    TwoOfThree,
+
+    #[id = "3:3"]
+    #[name = "3:3"]
+    ThreeOfThree,
+
+    #[id = "1:4"]
+    #[name = "1:4"]
+    OneOfFour,
+
+    #[id = "2:4"]
+    #[name = "2:4"]
+    TwoOfFour,
+
+    #[id = "3:4"]
+    #[name = "3:4"]
+    ThreeOfFour,
+
+    #[id = "4:4"]
+    #[name = "4:4"]
+    FourOfFour,
+
+    #[id = "!1:2"]
+    #[name = "Not 1:2"]
+    NotOneOfTwo,
+
+    #[id = "!2:2"]
+    #[name = "Not 2:2"]
+    NotTwoOfTwo,
+
+    #[id = "!1:3"]
+    #[name = "Not 1:3"]
+    NotOneOfThree,
+
+    #[id = "!2:3"]
+    #[name = "Not 2:3"]
+    NotTwoOfThree,
+
+    #[id = "!3:3"]
+    #[name = "Not 3:3"]
+    NotThreeOfThree,
+
+    #[id = "!1:4"]
+    #[name = "Not 1:4"]
+    NotOneOfFour,
+
+    #[id = "!2:4"]
+    #[name = "Not 2:4"]
+    NotTwoOfFour,
+
+    #[id = "!3:4"]
+    #[name = "Not 3:4"]
+    NotThreeOfFour,
+
+    #[id = "!4:4"]
+    #[name = "Not 4:4"]
+    NotFourOfFour,
+}
+
+impl TrigCondition {
+    // whether a step with this condition plays on the given loop of its track,
+    // counting loops from when its pattern started playing
+    pub fn passes(self, iteration: i64, fill: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Fill => fill,
+            Self::NotFill => !fill,
+            Self::First => iteration == 0,
+            Self::NotFirst => iteration != 0,
+            _ => match self.ratio() {
+                Some((a, b, not)) => (iteration.rem_euclid(b) == a - 1) != not,
+                None => true,
+            },
+        }
+    }
+
+    // loop a step plays on, the number of loops it counts over, and
+    // whether the condition is inverted
+    fn ratio(self) -> Option<(i64, i64, bool)> {
+        let ratio = match self {
+            Self::OneOfTwo => (1, 2, false),
+            Self::TwoOfTwo => (2, 2, false),
+            Self::OneOfThree => (1, 3, false),
+            Self::TwoOfThree => (2, 3, false),
+            Self::ThreeOfThree => (3, 3, false),
+            Self::OneOfFour => (1, 4, false),
+            Self::TwoOfFour => (2, 4, false),
+            Self::ThreeOfFour => (3, 4, false),
+            Self::FourOfFour => (4, 4, false),
+            Self::NotOneOfTwo => (1, 2, true),
+            Self::NotTwoOfTwo => (2, 2, true),
+            Self::NotOneOfThree => (1, 3, true),
+            Self::NotTwoOfThree => (2, 3, true),
+            Self::NotThreeOfThree => (3, 3, true),
+            Self::NotOneOfFour => (1, 4, true),
+            Self::NotTwoOfFour => (2, 4, true),
+            Self::NotThreeOfFour => (3, 4, true),
+            Self::NotFourOfFour => (4, 4, true),
+            _ => return None,
+        };
+        Some(ratio)
+    }
+}
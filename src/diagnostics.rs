@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+// number of messages kept for the editor, older ones are overwritten
+const CAPACITY: usize = 64;
+
+// something the audio thread reports to the editor, so the user can see
+// why the sequencer is silent without looking at the host's log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Diagnostic {
+    Stopped,
+    Playing,
+    Preroll,
+    MissingPosition,
+    FallbackTempo { tempo: f32 },
+    MissingSampleRate,
+    Relocated { pos_beats: f32 },
+    QueueFull,
+}
+
+impl Diagnostic {
+    // packed into a single atomic, zero is an empty slot
+    fn encode(self) -> u64 {
+        let (code, value) = match self {
+            Self::Stopped => (1, 0.0),
+            Self::Playing => (2, 0.0),
+            Self::Preroll => (3, 0.0),
+            Self::MissingPosition => (4, 0.0),
+            Self::FallbackTempo { tempo } => (5, tempo),
+            Self::MissingSampleRate => (6, 0.0),
+            Self::Relocated { pos_beats } => (7, pos_beats),
+            Self::QueueFull => (8, 0.0),
+        };
+        (code << 32) | value.to_bits() as u64
+    }
+
+    fn decode(entry: u64) -> Option<Self> {
+        let value = f32::from_bits(entry as u32);
+        match entry >> 32 {
+            1 => Some(Self::Stopped),
+            2 => Some(Self::Playing),
+            3 => Some(Self::Preroll),
+            4 => Some(Self::MissingPosition),
+            5 => Some(Self::FallbackTempo { tempo: value }),
+            6 => Some(Self::MissingSampleRate),
+            7 => Some(Self::Relocated { pos_beats: value }),
+            8 => Some(Self::QueueFull),
+            _ => None,
+        }
+    }
+
+    // something that happened, rather than the state the sequencer is in
+    fn is_event(self) -> bool {
+        matches!(self, Self::Relocated { .. } | Self::QueueFull)
+    }
+
+    // shown in the editor
+    pub fn message(self) -> String {
+        match self {
+            Self::Stopped => "Transport stopped".to_string(),
+            Self::Playing => "Playing".to_string(),
+            Self::Preroll => "Waiting for the host's pre-roll to end".to_string(),
+            Self::MissingPosition => "The host doesn't give the song position".to_string(),
+            Self::FallbackTempo { tempo } => {
+                format!("The host doesn't give the tempo, playing at {tempo:.1} BPM")
+            }
+            Self::MissingSampleRate => "The sample rate isn't known yet".to_string(),
+            Self::Relocated { pos_beats } => format!("Transport moved to beat {pos_beats:.2}"),
+            Self::QueueFull => "Too many notes waiting, some were dropped".to_string(),
+        }
+    }
+}
+
+// how well the sequencer has kept time with the host
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SyncCounters {
+    // step boundaries passed without the step being played
+    pub missed_steps: u64,
+
+    // steps whose boundary fell between buffers, played at the start of the next buffer
+    pub late_steps: u64,
+
+    // notes already due when they were scheduled, sent at the start of the buffer
+    pub late_notes: u64,
+}
+
+// the latest diagnostics, written by the audio thread and read by the editor
+// without locking. a state reported every buffer only shows up when it begins,
+// and an event only when it isn't the same as the one before it.
+pub struct Diagnostics {
+    entries: [AtomicU64; CAPACITY],
+
+    // number of entries ever written, only the audio thread writes
+    written: AtomicUsize,
+
+    // the state reported last, which events don't change
+    last_state: AtomicU64,
+
+    // added to by the audio thread, cleared from the editor
+    missed_steps: AtomicU64,
+    late_steps: AtomicU64,
+    late_notes: AtomicU64,
+}
+
+impl Diagnostics {
+    pub fn report(&self, diagnostic: Diagnostic) {
+        let entry = diagnostic.encode();
+        let written = self.written.load(Ordering::Relaxed);
+        let repeated = if diagnostic.is_event() {
+            written > 0 && self.entries[(written - 1) % CAPACITY].load(Ordering::Relaxed) == entry
+        } else {
+            self.last_state.swap(entry, Ordering::Relaxed) == entry
+        };
+        if repeated {
+            return;
+        }
+
+        self.entries[written % CAPACITY].store(entry, Ordering::Relaxed);
+        self.written.store(written + 1, Ordering::Release);
+    }
+
+    pub fn count(&self, counters: SyncCounters) {
+        self.missed_steps
+            .fetch_add(counters.missed_steps, Ordering::Relaxed);
+        self.late_steps
+            .fetch_add(counters.late_steps, Ordering::Relaxed);
+        self.late_notes
+            .fetch_add(counters.late_notes, Ordering::Relaxed);
+    }
+
+    pub fn counters(&self) -> SyncCounters {
+        SyncCounters {
+            missed_steps: self.missed_steps.load(Ordering::Relaxed),
+            late_steps: self.late_steps.load(Ordering::Relaxed),
+            late_notes: self.late_notes.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn clear_counters(&self) {
+        self.missed_steps.store(0, Ordering::Relaxed);
+        self.late_steps.store(0, Ordering::Relaxed);
+        self.late_notes.store(0, Ordering::Relaxed);
+    }
+
+    // the diagnostics still held, oldest first
+    pub fn recent(&self) -> Vec<Diagnostic> {
+        let written = self.written.load(Ordering::Acquire);
+        (written.saturating_sub(CAPACITY)..written)
+            .filter_map(|index| {
+                Diagnostic::decode(self.entries[index % CAPACITY].load(Ordering::Relaxed))
+            })
+            .collect()
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self {
+            entries: std::array::from_fn(|_| AtomicU64::new(0)),
+            written: AtomicUsize::new(0),
+            last_state: AtomicU64::new(0),
+            missed_steps: AtomicU64::new(0),
+            late_steps: AtomicU64::new(0),
+            late_notes: AtomicU64::new(0),
+        }
+    }
+}
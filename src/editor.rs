@@ -0,0 +1,1201 @@
+use nih_plug::prelude::*;
+use nih_plug_egui::egui::{
+    self, Align2, Color32, CursorIcon, FontId, Pos2, Rect, Sense, Stroke, Ui, Vec2,
+};
+use nih_plug_egui::{create_egui_editor, EguiState};
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::diagnostics::Diagnostics;
+use crate::groove::Groove;
+use crate::note_names::NoteNames;
+use crate::pattern::{Pattern, Step, Track};
+use crate::preset::{self, Preset, PresetSettings};
+use crate::rate::Rate;
+use crate::scale::{Scale, PITCH_CLASS_NAMES};
+use crate::{midi_file, MyPlugin, MyPluginParams, StepParams, Task};
+
+// steps are laid out in rows of a bar of sixteenths
+const STEPS_PER_ROW: usize = 16;
+const STEP_SIZE: Vec2 = Vec2::new(40.0, 40.0);
+const STEP_SPACING: f32 = 4.0;
+const STEP_ROUNDING: f32 = 4.0;
+
+// pixels of vertical drag that move a step's note by a semitone
+const DRAG_PIXELS_PER_NOTE: f32 = 4.0;
+
+// the gate is drawn along the bottom of a step and set by dragging its right edge
+const GATE_HANDLE_WIDTH: f32 = 6.0;
+const GATE_BAR_HEIGHT: f32 = 4.0;
+const GATE_BAR_COLOR: Color32 = Color32::from_rgb(0x60, 0x30, 0x08);
+
+const STEP_ON_COLOR: Color32 = Color32::from_rgb(0xe0, 0x8a, 0x2c);
+const STEP_OFF_COLOR: Color32 = Color32::from_gray(0x3a);
+const STEP_ON_TEXT_COLOR: Color32 = Color32::from_gray(0x10);
+const STEP_OFF_TEXT_COLOR: Color32 = Color32::from_gray(0x90);
+
+// step of the edited track being played, shared by the audio thread
+pub const NO_PLAYHEAD: i32 = -1;
+const PLAYHEAD_STROKE: Stroke = Stroke {
+    width: 2.0,
+    color: Color32::from_rgb(0xf0, 0xf0, 0xf0),
+};
+const PLAYHEAD_COLUMN_COLOR: Color32 = Color32::from_rgba_premultiplied(0x30, 0x30, 0x30, 0x30);
+
+// the velocity lane spans the width of a row of the step grid
+const VELOCITY_LANE_HEIGHT: f32 = 80.0;
+const VELOCITY_LANE_COLOR: Color32 = Color32::from_gray(0x24);
+const VELOCITY_OFF_COLOR: Color32 = Color32::from_gray(0x60);
+
+const CC_LANE_HEIGHT: f32 = 60.0;
+const CC_BAR_COLOR: Color32 = Color32::from_rgb(0x2c, 0x8a, 0xe0);
+
+// the piano roll shows a few octaves at a time, moved up and down an octave at a time
+const ROLL_OCTAVES: u8 = 4;
+const ROLL_DEFAULT_LOW_NOTE: u8 = 36;
+const ROLL_NOTE_HEIGHT: f32 = 8.0;
+const ROLL_STEP_WIDTH: f32 = 20.0;
+const ROLL_KEYBOARD_WIDTH: f32 = 36.0;
+
+const ROLL_ROW_COLOR: Color32 = Color32::from_gray(0x24);
+const ROLL_SCALE_ROW_COLOR: Color32 = Color32::from_gray(0x34);
+const ROLL_WHITE_KEY_COLOR: Color32 = Color32::from_gray(0xd8);
+const ROLL_BLACK_KEY_COLOR: Color32 = Color32::from_gray(0x18);
+const ROLL_KEY_TEXT_COLOR: Color32 = Color32::from_gray(0x40);
+const ROLL_NAME_TEXT_COLOR: Color32 = Color32::from_gray(0xa0);
+const ROLL_STEP_OFF_COLOR: Color32 = Color32::from_gray(0x60);
+
+// the diagnostics are read again this often while they're shown
+const DIAGNOSTICS_REFRESH: Duration = Duration::from_millis(250);
+
+// the result of a midi export is looked for this often until it arrives
+const EXPORT_REFRESH: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, PartialEq)]
+enum View {
+    Steps,
+    PianoRoll,
+    Presets,
+    Diagnostics,
+}
+
+// editor state that isn't kept with the plugin's state
+struct EditorState {
+    view: View,
+
+    // cc lane shown under the velocity lane
+    cc_lane: usize,
+
+    // lowest note shown in the piano roll
+    roll_low_note: u8,
+
+    // name the selected pattern is saved under
+    preset_name: String,
+
+    // saved presets, read again whenever the preset list is shown
+    presets: Option<Vec<String>>,
+
+    // result of the last preset action
+    preset_status: String,
+
+    // a midi export was started and its result hasn't been shown yet
+    exporting: bool,
+
+    // midi file imported into the edited track and the step length its notes are moved onto
+    import_path: String,
+    import_rate: Rate,
+
+    // json file the selected pattern is written to or read from
+    json_path: String,
+
+    // note name map shown in place of note numbers
+    note_names_path: String,
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        Self {
+            view: View::Steps,
+            cc_lane: 0,
+            roll_low_note: ROLL_DEFAULT_LOW_NOTE,
+            preset_name: String::new(),
+            presets: None,
+            preset_status: String::new(),
+            exporting: false,
+            import_path: String::new(),
+            import_rate: Rate::Sixteenth,
+            json_path: String::new(),
+            note_names_path: String::new(),
+        }
+    }
+}
+
+pub fn default_state() -> Arc<EguiState> {
+    EguiState::from_size(720, 560)
+}
+
+pub fn create(
+    params: Arc<MyPluginParams>,
+    playhead: Arc<AtomicI32>,
+    diagnostics: Arc<Diagnostics>,
+    incoming_pattern: Arc<Mutex<Option<Pattern>>>,
+    export_status: Arc<Mutex<Option<String>>>,
+    async_executor: AsyncExecutor<MyPlugin>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor_state.clone(),
+        EditorState::default(),
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            let playhead = usize::try_from(playhead.load(Ordering::Relaxed)).ok();
+            let note_names = note_names(&params);
+
+            // keep following the playhead, the frame after it stops clears it
+            if playhead.is_some() {
+                egui_ctx.request_repaint();
+            }
+
+            egui::CentralPanel::default().show(egui_ctx, |ui| {
+                ui.heading("Note Sequencer");
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Pattern {}, Track {}",
+                        params.pattern.value(),
+                        params.edit_track.value()
+                    ));
+                    ui.separator();
+                    ui.selectable_value(&mut state.view, View::Steps, "Steps");
+                    ui.selectable_value(&mut state.view, View::PianoRoll, "Piano Roll");
+                    if ui
+                        .selectable_value(&mut state.view, View::Presets, "Presets")
+                        .clicked()
+                    {
+                        state.presets = None;
+                    }
+                    ui.selectable_value(&mut state.view, View::Diagnostics, "Diagnostics");
+                });
+                ui.add_space(8.0);
+
+                match state.view {
+                    View::Steps => {
+                        step_grid(ui, setter, &params, &note_names, playhead);
+                        ui.add_space(8.0);
+                        velocity_lane(ui, setter, &params);
+                        ui.add_space(8.0);
+                        cc_lane(ui, setter, &params, state);
+                    }
+                    View::PianoRoll => {
+                        piano_roll(ui, setter, &params, &note_names, state, playhead)
+                    }
+                    View::Presets => {
+                        presets(ui, setter, &params, state, &incoming_pattern);
+                        ui.separator();
+                        export(ui, &params, state, &async_executor, &export_status);
+                        import(ui, setter, &params, state, &incoming_pattern);
+                        json(ui, setter, &params, state, &incoming_pattern);
+                        note_name_map(ui, &params, state);
+                    }
+                    View::Diagnostics => {
+                        diagnostics_console(ui, &diagnostics);
+                        egui_ctx.request_repaint_after(DIAGNOSTICS_REFRESH);
+                    }
+                }
+            });
+        },
+    )
+}
+
+// the steps of the edited track, edited through the step parameters
+// so the changes reach the pattern the same way host automation does
+fn step_grid(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    note_names: &NoteNames,
+    playhead: Option<usize>,
+) {
+    let num_steps = params.num_steps.value() as usize;
+
+    egui::Grid::new("step_grid")
+        .spacing(Vec2::splat(STEP_SPACING))
+        .show(ui, |ui| {
+            for (index, step) in params.steps.iter().take(num_steps).enumerate() {
+                let next_step = params.steps[..num_steps].get(index + 1);
+                let tied_to_next = next_step
+                    .is_some_and(|next_step| next_step.enabled.value() && next_step.tie.value());
+
+                let rect = step_cell(
+                    ui,
+                    setter,
+                    step,
+                    playhead == Some(index),
+                    tied_to_next,
+                    params.drum_mode.value(),
+                    note_names,
+                );
+                gate_handle(ui, setter, params, index, rect);
+
+                if (index + 1) % STEPS_PER_ROW == 0 {
+                    ui.end_row();
+                }
+            }
+        });
+}
+
+// a bar for the velocity of each step of the edited track, dragging across the
+// lane sets the velocity of every step the pointer passes over
+fn velocity_lane(ui: &mut Ui, setter: &ParamSetter, params: &MyPluginParams) {
+    let num_steps = params.num_steps.value() as usize;
+
+    let width = STEPS_PER_ROW as f32 * (STEP_SIZE.x + STEP_SPACING) - STEP_SPACING;
+    let (rect, response) = ui.allocate_exact_size(
+        Vec2::new(width, VELOCITY_LANE_HEIGHT),
+        Sense::click_and_drag(),
+    );
+    let bar_width = rect.width() / num_steps as f32;
+
+    // steps changed by the current drag, one bit per step, so each
+    // step's change is a single gesture for the host
+    let touched_id = response.id.with("touched");
+    let mut touched = ui
+        .data(|data| data.get_temp::<u64>(touched_id))
+        .unwrap_or(0);
+
+    if response.is_pointer_button_down_on() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let index = ((pos.x - rect.left()) / bar_width).floor() as usize;
+            if let Some(step) = params.steps[..num_steps].get(index) {
+                if touched & (1 << index) == 0 {
+                    setter.begin_set_parameter(&step.velocity);
+                    touched |= 1 << index;
+                }
+
+                let velocity = ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0);
+                setter.set_parameter(&step.velocity, velocity);
+            }
+        }
+    } else {
+        for (index, step) in params.steps.iter().enumerate() {
+            if touched & (1 << index) != 0 {
+                setter.end_set_parameter(&step.velocity);
+            }
+        }
+        touched = 0;
+    }
+
+    ui.data_mut(|data| data.insert_temp(touched_id, touched));
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, STEP_ROUNDING, VELOCITY_LANE_COLOR);
+    for (index, step) in params.steps.iter().take(num_steps).enumerate() {
+        let height = step.velocity.value() * rect.height();
+        let bar = Rect::from_min_max(
+            Pos2::new(
+                rect.left() + index as f32 * bar_width,
+                rect.bottom() - height,
+            ),
+            Pos2::new(rect.left() + (index + 1) as f32 * bar_width, rect.bottom()),
+        );
+        let fill = if step.enabled.value() {
+            STEP_ON_COLOR
+        } else {
+            VELOCITY_OFF_COLOR
+        };
+        painter.rect_filled(bar.shrink2(Vec2::new(1.0, 0.0)), 0.0, fill);
+    }
+}
+
+// a bar for the value each step sends on one of the edited track's cc lanes.
+// dragging across the lane sets the value of every step the pointer passes over,
+// dragging with the secondary button stops the steps sending anything.
+fn cc_lane(ui: &mut Ui, setter: &ParamSetter, params: &MyPluginParams, state: &mut EditorState) {
+    let num_steps = params.num_steps.value() as usize;
+    let edit_track = params.edit_track.value() as usize - 1;
+
+    ui.horizontal(|ui| {
+        for (lane, lane_params) in params.tracks[edit_track].cc_lanes.iter().enumerate() {
+            let label = format!("CC {}", lane_params.number.value());
+            ui.selectable_value(&mut state.cc_lane, lane, label);
+        }
+    });
+    let lane = state.cc_lane;
+
+    let width = STEPS_PER_ROW as f32 * (STEP_SIZE.x + STEP_SPACING) - STEP_SPACING;
+    let (rect, response) =
+        ui.allocate_exact_size(Vec2::new(width, CC_LANE_HEIGHT), Sense::click_and_drag());
+    let bar_width = rect.width() / num_steps as f32;
+
+    // steps changed by the current drag, one bit per step, as in the velocity lane
+    let touched_id = response.id.with("touched");
+    let mut touched = ui
+        .data(|data| data.get_temp::<u64>(touched_id))
+        .unwrap_or(0);
+
+    let clearing = ui.input(|input| input.pointer.secondary_down());
+    if response.is_pointer_button_down_on() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let index = ((pos.x - rect.left()) / bar_width).floor() as usize;
+            if let Some(step) = params.steps[..num_steps].get(index) {
+                let param = &step.cc[lane].value;
+                if touched & (1 << index) == 0 {
+                    setter.begin_set_parameter(param);
+                    touched |= 1 << index;
+                }
+
+                let value = if clearing {
+                    -1
+                } else {
+                    (((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0) * 127.0).round()
+                        as i32
+                };
+                setter.set_parameter(param, value);
+            }
+        }
+    } else {
+        for (index, step) in params.steps.iter().enumerate() {
+            if touched & (1 << index) != 0 {
+                setter.end_set_parameter(&step.cc[lane].value);
+            }
+        }
+        touched = 0;
+    }
+
+    ui.data_mut(|data| data.insert_temp(touched_id, touched));
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, STEP_ROUNDING, VELOCITY_LANE_COLOR);
+    for (index, step) in params.steps.iter().take(num_steps).enumerate() {
+        let Some(value) = step.cc[lane].value() else {
+            continue;
+        };
+
+        let height = value as f32 / 127.0 * rect.height();
+        let bar = Rect::from_min_max(
+            Pos2::new(
+                rect.left() + index as f32 * bar_width,
+                rect.bottom() - height,
+            ),
+            Pos2::new(rect.left() + (index + 1) as f32 * bar_width, rect.bottom()),
+        );
+        painter.rect_filled(bar.shrink2(Vec2::new(1.0, 0.0)), 0.0, CC_BAR_COLOR);
+    }
+}
+
+// clicking a step switches it on or off, dragging it up or down changes its note.
+// in drum mode the track's note is fixed, so the step shows its velocity instead
+fn step_cell(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    step: &StepParams,
+    playing: bool,
+    tied_to_next: bool,
+    drum_mode: bool,
+    note_names: &NoteNames,
+) -> Rect {
+    let sense = if drum_mode {
+        Sense::click()
+    } else {
+        Sense::click_and_drag()
+    };
+    let (rect, response) = ui.allocate_exact_size(STEP_SIZE, sense);
+
+    if response.clicked() {
+        setter.begin_set_parameter(&step.enabled);
+        setter.set_parameter(&step.enabled, !step.enabled.value());
+        setter.end_set_parameter(&step.enabled);
+    }
+
+    if response.drag_started() {
+        // the note is worked out from where the drag began, so slow drags still move it
+        ui.data_mut(|data| data.insert_temp(response.id, step.note.value()));
+        setter.begin_set_parameter(&step.note);
+    }
+
+    if response.dragged() {
+        let start_note = ui
+            .data(|data| data.get_temp::<i32>(response.id))
+            .unwrap_or_else(|| step.note.value());
+        let drag_y =
+            ui.input(
+                |input| match (input.pointer.press_origin(), input.pointer.interact_pos()) {
+                    (Some(origin), Some(pos)) => pos.y - origin.y,
+                    _ => 0.0,
+                },
+            );
+
+        // dragging up raises the note
+        let note = (start_note - (drag_y / DRAG_PIXELS_PER_NOTE).round() as i32).clamp(0, 127);
+        if note != step.note.value() {
+            setter.set_parameter(&step.note, note);
+        }
+    }
+
+    if response.drag_stopped() {
+        setter.end_set_parameter(&step.note);
+    }
+
+    let (fill, text_color) = if step.enabled.value() {
+        (STEP_ON_COLOR, STEP_ON_TEXT_COLOR)
+    } else {
+        (STEP_OFF_COLOR, STEP_OFF_TEXT_COLOR)
+    };
+
+    let painter = ui.painter();
+    painter.rect_filled(rect, STEP_ROUNDING, fill);
+    if playing {
+        painter.rect_stroke(rect, STEP_ROUNDING, PLAYHEAD_STROKE);
+    }
+    let label = if drum_mode {
+        step.velocity.to_string()
+    } else {
+        match note_names.name(step.note.value() as u8) {
+            Some(name) => name.to_string(),
+            None => step.note.to_string(),
+        }
+    };
+    painter.text(
+        rect.center(),
+        Align2::CENTER_CENTER,
+        label,
+        FontId::proportional(12.0),
+        text_color,
+    );
+
+    // a note held into the next step runs on across the gap between them
+    if step.enabled.value() {
+        let gate_width = if tied_to_next {
+            rect.width() + STEP_SPACING
+        } else {
+            rect.width() * step.gate.value()
+        };
+        let gate_bar = Rect::from_min_size(
+            Pos2::new(rect.left(), rect.bottom() - GATE_BAR_HEIGHT),
+            Vec2::new(gate_width, GATE_BAR_HEIGHT),
+        );
+        painter.rect_filled(gate_bar, 0.0, GATE_BAR_COLOR);
+    }
+
+    rect
+}
+
+// steps whose parameters the current gate drag changed, one bit per step for each
+// parameter, so the whole drag is a single gesture per parameter for the host
+#[derive(Clone, Copy, Default)]
+struct GateDrag {
+    gate: u64,
+    enabled: u64,
+    tie: u64,
+    note: u64,
+}
+
+// dragging the right edge of a step sets its gate, dragging it on past the end
+// of the step ties the steps it reaches to it and sets the gate of the last one
+fn gate_handle(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    index: usize,
+    rect: Rect,
+) {
+    let num_steps = params.num_steps.value() as usize;
+    let step = &params.steps[index];
+
+    let handle = Rect::from_min_max(
+        Pos2::new(rect.right() - GATE_HANDLE_WIDTH, rect.top()),
+        rect.max,
+    );
+    let response = ui
+        .interact(handle, ui.id().with(("gate_handle", index)), Sense::drag())
+        .on_hover_cursor(CursorIcon::ResizeHorizontal);
+
+    let touched_id = response.id.with("touched");
+    let mut touched = ui
+        .data(|data| data.get_temp::<GateDrag>(touched_id))
+        .unwrap_or_default();
+
+    if response.drag_stopped() {
+        for (index, step) in params.steps.iter().enumerate() {
+            let bit = 1 << index;
+            if touched.gate & bit != 0 {
+                setter.end_set_parameter(&step.gate);
+            }
+            if touched.enabled & bit != 0 {
+                setter.end_set_parameter(&step.enabled);
+            }
+            if touched.tie & bit != 0 {
+                setter.end_set_parameter(&step.tie);
+            }
+            if touched.note & bit != 0 {
+                setter.end_set_parameter(&step.note);
+            }
+        }
+        ui.data_mut(|data| data.remove::<GateDrag>(touched_id));
+        return;
+    }
+
+    if !response.dragged() {
+        return;
+    }
+    let Some(pos) = response.interact_pointer_pos() else {
+        return;
+    };
+
+    // steps reached by the drag beyond this one
+    let step_width = STEP_SIZE.x + STEP_SPACING;
+    let drag_x = (pos.x - rect.left()).max(0.0);
+    let tied_steps = ((drag_x / step_width).floor() as usize).min(num_steps - 1 - index);
+    let gate = ((drag_x - tied_steps as f32 * step_width) / STEP_SIZE.x).clamp(Step::MIN_GATE, 1.0);
+
+    for tied_index in index + 1..=index + tied_steps {
+        let tied_step = &params.steps[tied_index];
+        drag_param(
+            setter,
+            &mut touched.enabled,
+            tied_index,
+            &tied_step.enabled,
+            true,
+        );
+        drag_param(setter, &mut touched.tie, tied_index, &tied_step.tie, true);
+        drag_param(
+            setter,
+            &mut touched.note,
+            tied_index,
+            &tied_step.note,
+            step.note.value(),
+        );
+    }
+    let last_index = index + tied_steps;
+    drag_param(
+        setter,
+        &mut touched.gate,
+        last_index,
+        &params.steps[last_index].gate,
+        gate,
+    );
+
+    // steps tied on by a longer drag are let go again
+    for untied_index in (last_index + 1..num_steps)
+        .take_while(|&untied_index| params.steps[untied_index].tie.value())
+    {
+        drag_param(
+            setter,
+            &mut touched.tie,
+            untied_index,
+            &params.steps[untied_index].tie,
+            false,
+        );
+    }
+
+    ui.data_mut(|data| data.insert_temp(touched_id, touched));
+}
+
+// set a parameter changed by a drag, starting its gesture the first time the drag touches it
+fn drag_param<P: Param>(
+    setter: &ParamSetter,
+    touched: &mut u64,
+    index: usize,
+    param: &P,
+    value: P::Plain,
+) where
+    P::Plain: PartialEq,
+{
+    if *touched & (1 << index) == 0 {
+        if param.modulated_plain_value() == value {
+            return;
+        }
+        setter.begin_set_parameter(param);
+        *touched |= 1 << index;
+    }
+    setter.set_parameter(param, value);
+}
+
+// save the selected pattern and the settings it's played with under a name,
+// and load saved presets into the selected pattern
+fn presets(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    state: &mut EditorState,
+    incoming_pattern: &Mutex<Option<Pattern>>,
+) {
+    ui.horizontal(|ui| {
+        ui.label("Name");
+        ui.text_edit_singleline(&mut state.preset_name);
+        if ui.button("Save").clicked() {
+            state.preset_status = match save_preset(params, &state.preset_name) {
+                Ok(()) => format!("Saved \"{}\"", state.preset_name.trim()),
+                Err(err) => format!("Couldn't save preset: {err}"),
+            };
+            state.presets = None;
+        }
+    });
+
+    if !state.preset_status.is_empty() {
+        ui.label(&state.preset_status);
+    }
+    ui.separator();
+
+    let presets = state.presets.get_or_insert_with(|| {
+        preset::list().unwrap_or_else(|err| {
+            nih_log!("couldn't list presets: {err}");
+            Vec::new()
+        })
+    });
+
+    let mut loaded = None;
+    egui::ScrollArea::vertical()
+        .id_source("presets")
+        .show(ui, |ui| {
+            if presets.is_empty() {
+                ui.label("No presets saved yet");
+            }
+
+            for name in presets.iter() {
+                ui.horizontal(|ui| {
+                    if ui.button("Load").clicked() {
+                        loaded = Some((name.clone(), preset::load(name)));
+                    }
+                    ui.label(name);
+                });
+            }
+        });
+
+    if let Some((name, result)) = loaded {
+        state.preset_status = match result {
+            Ok(preset) => {
+                load_preset(setter, params, preset, incoming_pattern);
+                state.preset_name = name.clone();
+                format!("Loaded \"{name}\"")
+            }
+            Err(err) => format!("Couldn't load preset: {err}"),
+        };
+    }
+}
+
+// write the selected pattern to a midi file on a background thread
+fn export(
+    ui: &mut Ui,
+    params: &MyPluginParams,
+    state: &mut EditorState,
+    async_executor: &AsyncExecutor<MyPlugin>,
+    export_status: &Mutex<Option<String>>,
+) {
+    // exports started by a pattern action report here as well
+    if let Some(status) = export_status
+        .lock()
+        .ok()
+        .and_then(|mut status| status.take())
+    {
+        state.preset_status = status;
+        state.exporting = false;
+        ui.ctx().request_repaint();
+    } else if state.exporting {
+        ui.ctx().request_repaint_after(EXPORT_REFRESH);
+    }
+
+    if ui.button("Export MIDI").clicked() {
+        let pattern = params.pattern.value() as usize - 1;
+        async_executor.execute_background(Task::ExportMidi { pattern });
+        state.preset_status = match midi_file::export_dir() {
+            Some(dir) => format!("Exporting pattern {} to {}", pattern + 1, dir.display()),
+            None => "Couldn't export: no user data folder".to_string(),
+        };
+        state.exporting = true;
+    }
+}
+
+// replace the edited track of the selected pattern with the notes of a midi file
+fn import(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    state: &mut EditorState,
+    incoming_pattern: &Mutex<Option<Pattern>>,
+) {
+    ui.horizontal(|ui| {
+        ui.label("MIDI File");
+        ui.text_edit_singleline(&mut state.import_path);
+
+        egui::ComboBox::from_id_source("import_rate")
+            .selected_text(Rate::variants()[state.import_rate.to_index()])
+            .show_ui(ui, |ui| {
+                for (index, name) in Rate::variants().iter().enumerate() {
+                    ui.selectable_value(&mut state.import_rate, Rate::from_index(index), *name);
+                }
+            });
+
+        if ui.button("Import").clicked() {
+            let path = state.import_path.trim();
+            state.preset_status =
+                match std::fs::read(path).and_then(|bytes| midi_file::read(&bytes)) {
+                    Ok(clip) => {
+                        let track = midi_file::quantize(&clip, state.import_rate);
+                        import_track(setter, params, track, incoming_pattern);
+                        format!("Imported {path}")
+                    }
+                    Err(err) => format!("Couldn't import {path}: {err}"),
+                };
+        }
+
+        // the feel of the file is kept as the user groove, which is then played
+        if ui.button("Extract Groove").clicked() {
+            let path = state.import_path.trim();
+            state.preset_status =
+                match std::fs::read(path).and_then(|bytes| midi_file::read(&bytes)) {
+                    Ok(clip) => {
+                        let groove = midi_file::extract_groove(&clip, state.import_rate);
+                        match params.user_groove.lock() {
+                            Ok(mut user_groove) => *user_groove = groove,
+                            Err(err) => *err.into_inner() = groove,
+                        }
+                        set_param(setter, &params.groove, Groove::User);
+                        format!("Extracted groove from {path}")
+                    }
+                    Err(err) => format!("Couldn't extract groove from {path}: {err}"),
+                };
+        }
+    });
+}
+
+// write the selected pattern to a json file, or replace it with one read from a json file
+fn json(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    state: &mut EditorState,
+    incoming_pattern: &Mutex<Option<Pattern>>,
+) {
+    ui.horizontal(|ui| {
+        ui.label("JSON File");
+        ui.text_edit_singleline(&mut state.json_path);
+        let path = Path::new(state.json_path.trim());
+
+        if ui.button("Export").clicked() {
+            state.preset_status = match preset::export_pattern(path, &selected_pattern(params)) {
+                Ok(()) => format!("Exported {}", path.display()),
+                Err(err) => format!("Couldn't export {}: {err}", path.display()),
+            };
+        }
+
+        if ui.button("Import").clicked() {
+            state.preset_status = match preset::import_pattern(path) {
+                Ok(pattern) => {
+                    set_pattern_params(setter, params, &pattern);
+                    hand_over(incoming_pattern, pattern);
+                    format!("Imported {}", path.display())
+                }
+                Err(err) => format!("Couldn't import {}: {err}", path.display()),
+            };
+        }
+    });
+}
+
+// read a note name map from a json or text file, shown from then on in place of note numbers
+fn note_name_map(ui: &mut Ui, params: &MyPluginParams, state: &mut EditorState) {
+    ui.horizontal(|ui| {
+        ui.label("Note Names");
+        ui.text_edit_singleline(&mut state.note_names_path);
+        let path = Path::new(state.note_names_path.trim());
+
+        if ui.button("Load").clicked() {
+            state.preset_status = match NoteNames::load(path) {
+                Ok(names) => {
+                    let status = format!("Loaded {} note names", names.num_names());
+                    set_note_names(params, names);
+                    status
+                }
+                Err(err) => format!("Couldn't load {}: {err}", path.display()),
+            };
+        }
+
+        if ui.button("Clear").clicked() {
+            set_note_names(params, NoteNames::default());
+            state.preset_status = "Cleared note names".to_string();
+        }
+    });
+}
+
+fn note_names(params: &MyPluginParams) -> NoteNames {
+    match params.note_names.lock() {
+        Ok(note_names) => note_names.clone(),
+        Err(err) => err.into_inner().clone(),
+    }
+}
+
+fn set_note_names(params: &MyPluginParams, names: NoteNames) {
+    match params.note_names.lock() {
+        Ok(mut note_names) => *note_names = names,
+        Err(err) => *err.into_inner() = names,
+    }
+}
+
+// contents of the selected pattern as last copied from the audio thread
+fn selected_pattern(params: &MyPluginParams) -> Pattern {
+    let selected_pattern = params.pattern.value() as usize - 1;
+    match params.patterns.lock() {
+        Ok(patterns) => patterns[selected_pattern].clone(),
+        Err(err) => err.into_inner()[selected_pattern].clone(),
+    }
+}
+
+// the audio thread puts the pattern in the selected slot
+fn hand_over(incoming_pattern: &Mutex<Option<Pattern>>, pattern: Pattern) {
+    match incoming_pattern.lock() {
+        Ok(mut incoming_pattern) => *incoming_pattern = Some(pattern),
+        Err(err) => *err.into_inner() = Some(pattern),
+    }
+}
+
+// the pattern is handed to the audio thread, and the edited track's parameters
+// are set to match so the editor shows the imported steps
+fn import_track(
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    track: Track,
+    incoming_pattern: &Mutex<Option<Pattern>>,
+) {
+    let edit_track = params.edit_track.value() as usize - 1;
+    let mut pattern = selected_pattern(params);
+    set_track_params(setter, params, &track);
+    pattern.tracks[edit_track] = track;
+    hand_over(incoming_pattern, pattern);
+}
+
+// set the parameters of the selected pattern to show a pattern loaded in its place,
+// along with the steps of its edited track
+fn set_pattern_params(setter: &ParamSetter, params: &MyPluginParams, pattern: &Pattern) {
+    let edit_track = params.edit_track.value() as usize - 1;
+    set_track_params(setter, params, &pattern.tracks[edit_track]);
+    set_param(setter, &params.seed, pattern.seed as i32);
+    set_param(
+        setter,
+        &params.program,
+        pattern.program.map_or(-1, |program| program as i32),
+    );
+    set_param(setter, &params.follow, pattern.follow);
+    set_param(
+        setter,
+        &params.follow_repeats,
+        pattern.follow_repeats as i32,
+    );
+    set_param(
+        setter,
+        &params.follow_pattern,
+        pattern.follow_pattern as i32 + 1,
+    );
+}
+
+// set the parameters of the edited track to show the given track's steps
+fn set_track_params(setter: &ParamSetter, params: &MyPluginParams, track: &Track) {
+    set_param(setter, &params.num_steps, track.len as i32);
+    set_param(setter, &params.rate, track.rate);
+    for (step_params, step) in params.steps.iter().zip(&track.steps) {
+        set_step_params(setter, step_params, step);
+    }
+}
+
+fn set_step_params(setter: &ParamSetter, step_params: &StepParams, step: &Step) {
+    set_param(setter, &step_params.enabled, step.enabled);
+    set_param(setter, &step_params.note, step.note as i32);
+    set_param(setter, &step_params.velocity, step.velocity);
+    set_param(setter, &step_params.gate, step.gate);
+    set_param(setter, &step_params.accent, step.accent);
+    set_param(setter, &step_params.velocity_range, step.velocity_range);
+    set_param(setter, &step_params.probability, step.probability);
+    set_param(setter, &step_params.variation, step.variation);
+    set_param(setter, &step_params.condition, step.condition);
+    set_param(setter, &step_params.ratchets, step.ratchets as i32);
+    set_param(setter, &step_params.tie, step.tie);
+    set_param(setter, &step_params.slide, step.slide);
+    set_param(setter, &step_params.offset, step.offset);
+    set_param(setter, &step_params.chord_type, step.chord_type);
+    for (chord_note_params, chord_note) in step_params.chord_notes.iter().zip(step.chord_notes) {
+        set_param(
+            setter,
+            &chord_note_params.note,
+            chord_note.map_or(-1, |note| note as i32),
+        );
+    }
+    for (pool_params, pool_note) in step_params.pool.iter().zip(step.pool) {
+        set_param(
+            setter,
+            &pool_params.note,
+            pool_note.map_or(-1, |pool_note| pool_note.note as i32),
+        );
+        if let Some(pool_note) = pool_note {
+            set_param(setter, &pool_params.weight, pool_note.weight);
+        }
+    }
+    set_param(
+        setter,
+        &step_params.pressure,
+        step.pressure.map_or(-1, |pressure| pressure as i32),
+    );
+    set_param(setter, &step_params.volume, step.volume);
+    set_param(setter, &step_params.pan, step.pan);
+    set_param(setter, &step_params.tuning, step.tuning);
+    for (cc_params, cc) in step_params.cc.iter().zip(step.cc) {
+        set_param(
+            setter,
+            &cc_params.value,
+            cc.map_or(-1, |value| value as i32),
+        );
+    }
+}
+
+fn save_preset(params: &MyPluginParams, name: &str) -> std::io::Result<()> {
+    let preset = Preset {
+        pattern: selected_pattern(params),
+        settings: PresetSettings {
+            swing: params.swing.value(),
+            scale: params.scale.value(),
+            scale_root: params.scale_root.value(),
+            custom_scale: params.custom_scale.value() as u16,
+            key: params.key.value(),
+        },
+    };
+    preset::save(name, &preset)
+}
+
+// the pattern is handed to the audio thread, which puts it in the selected slot
+fn load_preset(
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    preset: Preset,
+    incoming_pattern: &Mutex<Option<Pattern>>,
+) {
+    set_pattern_params(setter, params, &preset.pattern);
+    hand_over(incoming_pattern, preset.pattern);
+
+    let settings = preset.settings;
+    set_param(setter, &params.swing, settings.swing);
+    set_param(setter, &params.scale, settings.scale);
+    set_param(setter, &params.scale_root, settings.scale_root);
+    set_param(setter, &params.custom_scale, settings.custom_scale as i32);
+    set_param(setter, &params.key, settings.key);
+}
+
+// change a parameter in a single gesture, if the value is different
+fn set_param<P: Param>(setter: &ParamSetter, param: &P, value: P::Plain)
+where
+    P::Plain: PartialEq,
+{
+    if param.modulated_plain_value() != value {
+        setter.begin_set_parameter(param);
+        setter.set_parameter(param, value);
+        setter.end_set_parameter(param);
+    }
+}
+
+// the pitch of each step of the edited track against a keyboard, with the notes of the
+// scale highlighted. clicking a row moves the step to that note and switches it on,
+// dragging a step moves it to the row under the pointer
+fn piano_roll(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    params: &MyPluginParams,
+    note_names: &NoteNames,
+    state: &mut EditorState,
+    playhead: Option<usize>,
+) {
+    // drum tracks play a fixed note, so there's no pitch to edit
+    if params.drum_mode.value() {
+        ui.label("Drum mode plays each track's drum note, edit the steps in the step view");
+        return;
+    }
+
+    let num_steps = params.num_steps.value() as usize;
+    let scale = params.pitch_classes();
+    let root = params.scale_root.value() as u8;
+
+    let max_low_note = 127 - ROLL_OCTAVES * 12 + 1;
+    ui.horizontal(|ui| {
+        if ui.button("Octave Down").clicked() {
+            state.roll_low_note = state.roll_low_note.saturating_sub(12);
+        }
+        if ui.button("Octave Up").clicked() {
+            state.roll_low_note = (state.roll_low_note + 12).min(max_low_note);
+        }
+    });
+
+    if params.scale.value() == Scale::Custom {
+        custom_scale(ui, setter, params);
+    }
+
+    let low_note = state.roll_low_note;
+    let num_notes = ROLL_OCTAVES * 12;
+    let high_note = low_note + num_notes - 1;
+
+    let size = Vec2::new(
+        ROLL_KEYBOARD_WIDTH + num_steps as f32 * ROLL_STEP_WIDTH,
+        num_notes as f32 * ROLL_NOTE_HEIGHT,
+    );
+
+    egui::ScrollArea::horizontal()
+        .id_source("piano_roll")
+        .show(ui, |ui| {
+            let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+            let row_top = |note: u8| rect.top() + (high_note - note) as f32 * ROLL_NOTE_HEIGHT;
+
+            // note of the row at the given height, kept within the rows shown
+            let row_note = |y: f32| {
+                let row = ((y - rect.top()) / ROLL_NOTE_HEIGHT).floor() as i32;
+                (high_note as i32 - row).clamp(low_note as i32, high_note as i32)
+            };
+
+            let painter = ui.painter_at(rect);
+            for note in low_note..=high_note {
+                let row = Rect::from_min_size(
+                    Pos2::new(rect.left(), row_top(note)),
+                    Vec2::new(rect.width(), ROLL_NOTE_HEIGHT),
+                );
+                let row_color = if scale.contains(note, root) {
+                    ROLL_SCALE_ROW_COLOR
+                } else {
+                    ROLL_ROW_COLOR
+                };
+                painter.rect_filled(row, 0.0, row_color);
+
+                let key =
+                    Rect::from_min_size(row.min, Vec2::new(ROLL_KEYBOARD_WIDTH, ROLL_NOTE_HEIGHT));
+                let key_color = if is_black_key(note) {
+                    ROLL_BLACK_KEY_COLOR
+                } else {
+                    ROLL_WHITE_KEY_COLOR
+                };
+                painter.rect_filled(key.shrink(0.5), 0.0, key_color);
+                if let Some(name) = note_names.name(note) {
+                    painter.text(
+                        key.right_center() + Vec2::new(2.0, 0.0),
+                        Align2::LEFT_CENTER,
+                        name,
+                        FontId::proportional(ROLL_NOTE_HEIGHT),
+                        ROLL_NAME_TEXT_COLOR,
+                    );
+                } else if note % 12 == 0 {
+                    painter.text(
+                        key.right_center() - Vec2::new(2.0, 0.0),
+                        Align2::RIGHT_CENTER,
+                        format!("C{}", note as i32 / 12 - 1),
+                        FontId::proportional(ROLL_NOTE_HEIGHT),
+                        ROLL_KEY_TEXT_COLOR,
+                    );
+                }
+            }
+
+            for (index, step) in params.steps.iter().take(num_steps).enumerate() {
+                let column = Rect::from_min_size(
+                    Pos2::new(
+                        rect.left() + ROLL_KEYBOARD_WIDTH + index as f32 * ROLL_STEP_WIDTH,
+                        rect.top(),
+                    ),
+                    Vec2::new(ROLL_STEP_WIDTH, rect.height()),
+                );
+                if playhead == Some(index) {
+                    painter.rect_filled(column, 0.0, PLAYHEAD_COLUMN_COLOR);
+                }
+
+                let response = ui.interact(
+                    column,
+                    ui.id().with(("piano_roll_step", index)),
+                    Sense::click_and_drag(),
+                );
+
+                if response.clicked() {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        setter.begin_set_parameter(&step.note);
+                        setter.set_parameter(&step.note, row_note(pos.y));
+                        setter.end_set_parameter(&step.note);
+                        if !step.enabled.value() {
+                            setter.begin_set_parameter(&step.enabled);
+                            setter.set_parameter(&step.enabled, true);
+                            setter.end_set_parameter(&step.enabled);
+                        }
+                    }
+                }
+
+                if response.drag_started() {
+                    setter.begin_set_parameter(&step.note);
+                }
+
+                if response.dragged() {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        let note = row_note(pos.y);
+                        if note != step.note.value() {
+                            setter.set_parameter(&step.note, note);
+                        }
+                    }
+                }
+
+                if response.drag_stopped() {
+                    setter.end_set_parameter(&step.note);
+                }
+
+                let note = step.note.value() as u8;
+                if (low_note..=high_note).contains(&note) {
+                    let fill = if step.enabled.value() {
+                        STEP_ON_COLOR
+                    } else {
+                        ROLL_STEP_OFF_COLOR
+                    };
+                    let note_rect = Rect::from_min_size(
+                        Pos2::new(column.left(), row_top(note)),
+                        Vec2::new(ROLL_STEP_WIDTH, ROLL_NOTE_HEIGHT),
+                    );
+                    painter.rect_filled(note_rect.shrink(1.0), 2.0, fill);
+                }
+            }
+        });
+}
+
+// a toggle for each note of the octave from the root up, switching it in or out of
+// the custom scale
+fn custom_scale(ui: &mut Ui, setter: &ParamSetter, params: &MyPluginParams) {
+    let mask = params.custom_scale.value() as u16;
+    let root = params.scale_root.value() as usize;
+    ui.horizontal(|ui| {
+        ui.label("Custom Scale");
+        for degree in 0..12 {
+            let bit = 1 << degree;
+            let name = PITCH_CLASS_NAMES[(root + degree) % 12];
+            if ui.selectable_label(mask & bit != 0, name).clicked() {
+                set_param(setter, &params.custom_scale, (mask ^ bit) as i32);
+            }
+        }
+    });
+}
+
+fn is_black_key(note: u8) -> bool {
+    matches!(note % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+// what the audio thread has reported, latest at the bottom
+fn diagnostics_console(ui: &mut Ui, diagnostics: &Diagnostics) {
+    let counters = diagnostics.counters();
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Missed steps: {}   Late steps: {}   Late notes: {}",
+            counters.missed_steps, counters.late_steps, counters.late_notes
+        ));
+        if ui.button("Clear").clicked() {
+            diagnostics.clear_counters();
+        }
+    });
+    ui.separator();
+
+    let recent = diagnostics.recent();
+    egui::ScrollArea::vertical()
+        .id_source("diagnostics")
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            if recent.is_empty() {
+                ui.label("Nothing reported yet");
+            }
+
+            for diagnostic in recent {
+                ui.label(diagnostic.message());
+            }
+        });
+}
@@ -0,0 +1,659 @@
+use crate::diagnostics::SyncCounters;
+use crate::grid::Grid;
+
+// the timing of the steps, kept apart from the plugin so it only deals in plain
+// numbers. it's given where the song is at the start of each buffer, how long the
+// buffer is and the sample rate, and works out where in the buffer steps start.
+// what the steps play is left to the plugin.
+
+// where the song is at the start of the current buffer
+pub struct TransportSnapshot {
+    pub pos_beats: f64,
+    pub pos_samples: Option<i64>,
+    pub tempo: f64,
+
+    // duration of a bar in beats
+    pub bar_beats: f64,
+
+    // worked out from the position when not given
+    pub bar_number: Option<i64>,
+    pub bar_start_beats: Option<f64>,
+}
+
+// transport state shared by all tracks for the current buffer
+pub struct Clock {
+    pub pos_beats: f64,
+    pub sample_rate: f64,
+
+    // offsets are worked out as a distance in beats from the position at the
+    // start of the buffer, converted at the tempo of this buffer
+    pub samples_per_beat: f64,
+    pub buffer_samples: usize,
+
+    // duration of a bar in beats
+    pub bar_beats: f64,
+
+    // start of the bar the buffer starts in, and its number counting from the start of the song
+    pub bar_start_beats: f64,
+    pub bar_number: i64,
+
+    // fraction of a step that swing delays even numbered steps by
+    pub swing_delay: f64,
+
+    // the transport was already playing during the previous buffer
+    pub was_playing: bool,
+
+    // the host looped back or the playhead was moved since the previous buffer
+    pub relocated: bool,
+
+    // where the previous buffer ended, when the transport carried on from it
+    pub last_end_beats: Option<f64>,
+
+    // the position comes from the internal clock
+    pub free_running: bool,
+}
+
+impl Clock {
+    // step grid of straight steps of the given duration in beats
+    pub fn grid(&self, step_beats: f64) -> Grid {
+        Grid::new(
+            step_beats,
+            self.bar_beats,
+            self.swing_delay,
+            self.bar_start_beats,
+            self.bar_number,
+        )
+    }
+
+    // the given position counted in beats from the start of the song as if every bar
+    // had the length of the current one, so anything repeating every so many beats
+    // lines up with the host's bar lines instead of with beat 0
+    pub fn bar_aligned_beats(&self, pos_beats: f64) -> f64 {
+        self.bar_number as f64 * self.bar_beats + (pos_beats - self.bar_start_beats)
+    }
+
+    // first step of the grid whose boundary isn't before the given position,
+    // a boundary rounding to the sample at the position counts as on it
+    fn first_step(&self, grid: &Grid, pos_beats: f64) -> i64 {
+        let step_count = grid.pos_steps(pos_beats).floor() as i64;
+        let offset = (grid.step_pos_beats(step_count) - pos_beats) * self.samples_per_beat;
+        if sample_index(offset) < 0 {
+            step_count + 1
+        } else {
+            step_count
+        }
+    }
+
+    // sample index within this buffer nearest the given position, which can lie outside it
+    pub fn timing(&self, pos_beats: f64) -> i64 {
+        sample_index((pos_beats - self.pos_beats) * self.samples_per_beat)
+    }
+
+    // sample index within this buffer of the boundary of the given step
+    fn step_timing(&self, grid: &Grid, step_count: i64) -> i64 {
+        self.timing(grid.step_pos_beats(step_count))
+    }
+
+    // the given step, beginning at the given sample index of this buffer
+    fn step_start(&self, grid: &Grid, step_beats: f64, timing: u32, step_count: i64) -> StepStart {
+        StepStart {
+            timing,
+            step_count,
+            step_samples: grid.step_len_beats(step_count) * self.samples_per_beat,
+            next_step_samples: grid.step_len_beats(step_count + 1) * self.samples_per_beat,
+            straight_step_samples: step_beats * self.samples_per_beat,
+            bar: grid.bar(step_count),
+            next_bar: grid.bar(step_count + 1),
+        }
+    }
+}
+
+// a step that begins within the current buffer
+pub struct StepStart {
+    // sample index relative to the start of the buffer
+    pub timing: u32,
+
+    // number of steps since the start of the song
+    pub step_count: i64,
+
+    // duration of the step in samples
+    pub step_samples: f64,
+
+    // duration of the following step in samples
+    pub next_step_samples: f64,
+
+    // duration of a step in samples before swing is applied
+    pub straight_step_samples: f64,
+
+    // bars since the start of the song for this step and the following step
+    pub bar: i64,
+    pub next_bar: i64,
+}
+
+pub struct Engine {
+    // the transport was playing during the previous buffer
+    playing: bool,
+
+    // position the next buffer starts at if the transport keeps playing without a jump
+    next_pos_beats: Option<f64>,
+    next_pos_samples: Option<i64>,
+
+    // steps lost or played late since the counters were last taken
+    counters: SyncCounters,
+}
+
+impl Engine {
+    // send all notes off
+    const DEFAULT_PLAYING: bool = true;
+
+    // positions closer than this to a bar line are treated as on it
+    const BAR_EPSILON: f64 = 1e-9;
+
+    // how far the transport can be from where it's expected to be before it counts as moved
+    const RELOCATE_TOLERANCE_SAMPLES: i64 = 1;
+    const RELOCATE_TOLERANCE_BUFFERS: f64 = 0.5;
+
+    // the transport isn't playing during this buffer, true for the first
+    // buffer after it stops, when the notes that were playing need ending
+    pub fn stop(&mut self) -> bool {
+        if !self.playing {
+            return false;
+        }
+
+        self.playing = false;
+        self.next_pos_beats = None;
+        self.next_pos_samples = None;
+        true
+    }
+
+    // transport state for a buffer of the given length starting at the given position
+    pub fn clock(
+        &mut self,
+        snapshot: &TransportSnapshot,
+        buffer_samples: usize,
+        sample_rate: f64,
+        swing_delay: f64,
+        free_running: bool,
+    ) -> Clock {
+        let &TransportSnapshot {
+            pos_beats,
+            pos_samples,
+            tempo,
+            bar_beats,
+            bar_number,
+            bar_start_beats,
+        } = snapshot;
+
+        let bar_number =
+            bar_number.unwrap_or((pos_beats / bar_beats + Self::BAR_EPSILON).floor() as i64);
+        let bar_start_beats = bar_start_beats.unwrap_or(bar_number as f64 * bar_beats);
+
+        let was_playing = self.playing;
+        self.playing = true;
+
+        // the host looped back or the playhead was moved, the sample position is exact
+        // where the host gives it, the beat position may drift with tempo changes
+        let samples_per_beat = sample_rate * 60.0 / tempo;
+        let buffer_beats = buffer_samples as f64 / samples_per_beat;
+        let relocated = match (pos_samples, self.next_pos_samples) {
+            (Some(pos_samples), Some(next_pos_samples)) => {
+                (pos_samples - next_pos_samples).abs() > Self::RELOCATE_TOLERANCE_SAMPLES
+            }
+            _ => self.next_pos_beats.is_some_and(|next_pos_beats| {
+                (pos_beats - next_pos_beats).abs() > buffer_beats * Self::RELOCATE_TOLERANCE_BUFFERS
+            }),
+        };
+        let last_end_beats = self.next_pos_beats.filter(|_| was_playing && !relocated);
+        self.next_pos_beats = Some(pos_beats + buffer_beats);
+        self.next_pos_samples = pos_samples.map(|pos_samples| pos_samples + buffer_samples as i64);
+
+        if relocated {
+            // the tracks start over from the new position as if play was just pressed
+            nih_log!("transport relocated to {pos_beats}");
+        }
+
+        Clock {
+            pos_beats,
+            sample_rate,
+            samples_per_beat,
+            buffer_samples,
+            bar_beats,
+            bar_start_beats,
+            bar_number,
+            swing_delay,
+            was_playing,
+            relocated,
+            last_end_beats,
+            free_running,
+        }
+    }
+
+    // the first step whose boundary falls within this buffer, if there is one. a buffer
+    // holds the boundaries that round to its samples, so a boundary on the end of one
+    // buffer is the start of the next one whatever the buffer sizes
+    pub fn find_step_start(&mut self, step_beats: f64, clock: &Clock) -> Option<StepStart> {
+        let grid = clock.grid(step_beats);
+        let mut step_count = clock.first_step(&grid, clock.pos_beats);
+
+        // the host's position can jump a little between buffers without counting as moved,
+        // boundaries it jumped past are played late and boundaries it jumped back over
+        // were already played in the previous buffer
+        if let Some(last_end_beats) = clock.last_end_beats {
+            let first_unplayed = clock.first_step(&grid, last_end_beats);
+            if first_unplayed < step_count {
+                nih_log!("missed buffer containing step start, setting timing to 0");
+                self.counters.late_steps += 1;
+                self.counters.missed_steps += (step_count - 1 - first_unplayed) as u64;
+                return Some(clock.step_start(&grid, step_beats, 0, step_count - 1));
+            }
+            step_count = first_unplayed;
+        }
+
+        let timing = clock.step_timing(&grid, step_count);
+        if timing >= clock.buffer_samples as i64 {
+            return None;
+        }
+
+        nih_log!("buffer contains start of step");
+        Some(clock.step_start(&grid, step_beats, timing as u32, step_count))
+    }
+
+    // the step following the given one, if it also starts within this buffer,
+    // which happens with short steps or long buffers
+    pub fn next_step_start(
+        &self,
+        step_beats: f64,
+        clock: &Clock,
+        step_start: &StepStart,
+    ) -> Option<StepStart> {
+        let grid = clock.grid(step_beats);
+        let step_count = step_start.step_count + 1;
+
+        let timing = clock.step_timing(&grid, step_count);
+        if timing >= clock.buffer_samples as i64 {
+            return None;
+        }
+
+        nih_log!("buffer contains start of another step");
+
+        // a step that began between buffers was moved to the start of this one
+        let timing = (timing as u32).max(step_start.timing);
+        Some(clock.step_start(&grid, step_beats, timing, step_count))
+    }
+
+    // the first step of a grid that takes over from the given sample index of this
+    // buffer on, when the step length changes part way through the buffer. the steps
+    // before it were found on the old grid.
+    pub fn find_step_start_from(
+        &self,
+        step_beats: f64,
+        clock: &Clock,
+        from_timing: u32,
+    ) -> Option<StepStart> {
+        let grid = clock.grid(step_beats);
+        let from_beats = clock.pos_beats + from_timing as f64 / clock.samples_per_beat;
+        let step_count = clock.first_step(&grid, from_beats);
+
+        let timing = clock.step_timing(&grid, step_count);
+        if timing >= clock.buffer_samples as i64 {
+            return None;
+        }
+
+        nih_log!("buffer contains start of step after step length change");
+        let timing = (timing as u32).max(from_timing);
+        Some(clock.step_start(&grid, step_beats, timing, step_count))
+    }
+
+    // steps lost or played late since this was last called
+    pub fn take_counters(&mut self) -> SyncCounters {
+        std::mem::take(&mut self.counters)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            playing: Self::DEFAULT_PLAYING,
+            next_pos_beats: None,
+            next_pos_samples: None,
+            counters: SyncCounters::default(),
+        }
+    }
+}
+
+// nearest sample to a distance in samples, halves round up so a boundary rounds the
+// same way measured from any buffer
+fn sample_index(samples: f64) -> i64 {
+    (samples + 0.5).floor() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    // sixteenth notes, 6000 samples long at 120 bpm
+    const STEP_BEATS: f64 = 0.25;
+    const STEP_SAMPLES: i64 = 6000;
+
+    // a host transport that plays one track through the engine
+    struct Host {
+        engine: Engine,
+        pos_samples: i64,
+        pos_beats: f64,
+        tempo: f64,
+
+        // the host gives its position in samples as well as in beats
+        has_samples: bool,
+
+        // the host's bars start this far from beat 0, and it says where they start
+        bar_offset_beats: Option<f64>,
+
+        // length of the steps played, and the new length the steps take from a position on
+        step_beats: f64,
+        step_change: Option<(f64, f64)>,
+    }
+
+    impl Host {
+        // hosts process buffers with the transport stopped before play is pressed
+        fn new() -> Self {
+            let mut engine = Engine::default();
+            engine.stop();
+
+            Self {
+                engine,
+                pos_samples: 0,
+                pos_beats: 0.0,
+                tempo: 120.0,
+                has_samples: true,
+                bar_offset_beats: None,
+                step_beats: STEP_BEATS,
+                step_change: None,
+            }
+        }
+
+        fn samples_per_beat(&self) -> f64 {
+            SAMPLE_RATE * 60.0 / self.tempo
+        }
+
+        // move the playhead to the given sample at the current tempo
+        fn seek(&mut self, pos_samples: i64) {
+            self.pos_samples = pos_samples;
+            self.pos_beats = pos_samples as f64 / self.samples_per_beat();
+        }
+
+        // process one buffer, giving the step count and song position
+        // in samples of every step that starts in it
+        fn process(&mut self, buffer_samples: usize) -> Vec<(i64, i64)> {
+            let bar = self
+                .bar_offset_beats
+                .map(|offset| (offset, ((self.pos_beats - offset) / 4.0).floor()));
+            let snapshot = TransportSnapshot {
+                pos_beats: self.pos_beats,
+                pos_samples: self.has_samples.then_some(self.pos_samples),
+                tempo: self.tempo,
+                bar_beats: 4.0,
+                bar_number: bar.map(|(_, bar)| bar as i64),
+                bar_start_beats: bar.map(|(offset, bar)| offset + bar * 4.0),
+            };
+            let clock = self
+                .engine
+                .clock(&snapshot, buffer_samples, SAMPLE_RATE, 0.0, false);
+
+            // the steps before a change in step length are found on the old grid,
+            // the ones from the change on on the new one
+            let mut change = None;
+            if let Some((pos_beats, step_beats)) = self.step_change {
+                let timing = clock.timing(pos_beats);
+                if timing <= 0 {
+                    self.step_beats = step_beats;
+                    self.step_change = None;
+                } else if timing < buffer_samples as i64 {
+                    change = Some((timing as u32, step_beats));
+                }
+            }
+            let until = change.map_or(buffer_samples as u32, |(timing, _)| timing);
+
+            let mut steps = Vec::new();
+            let mut next_step_start = self.engine.find_step_start(self.step_beats, &clock);
+            while let Some(step_start) = next_step_start.filter(|step| step.timing < until) {
+                steps.push((
+                    step_start.step_count,
+                    self.pos_samples + step_start.timing as i64,
+                ));
+                next_step_start = self
+                    .engine
+                    .next_step_start(self.step_beats, &clock, &step_start);
+            }
+
+            if let Some((timing, step_beats)) = change {
+                self.step_beats = step_beats;
+                self.step_change = None;
+                let mut next_step_start =
+                    self.engine
+                        .find_step_start_from(self.step_beats, &clock, timing);
+                while let Some(step_start) = next_step_start {
+                    steps.push((
+                        step_start.step_count,
+                        self.pos_samples + step_start.timing as i64,
+                    ));
+                    next_step_start =
+                        self.engine
+                            .next_step_start(self.step_beats, &clock, &step_start);
+                }
+            }
+
+            self.pos_samples += buffer_samples as i64;
+            self.pos_beats += buffer_samples as f64 / self.samples_per_beat();
+            steps
+        }
+
+        // process buffers of the given sizes in turn until the song reaches the given sample
+        fn play(&mut self, buffer_sizes: &[usize], until: i64) -> Vec<(i64, i64)> {
+            let mut steps = Vec::new();
+            for &buffer_samples in buffer_sizes.iter().cycle() {
+                if self.pos_samples >= until {
+                    break;
+                }
+                let buffer_samples = buffer_samples.min((until - self.pos_samples) as usize);
+                steps.extend(self.process(buffer_samples));
+            }
+            steps
+        }
+    }
+
+    // steps of 120 bpm sixteenths from first to last, as played from the start of the song
+    fn straight_steps(first: i64, last: i64) -> Vec<(i64, i64)> {
+        (first..=last)
+            .map(|step_count| (step_count, step_count * STEP_SAMPLES))
+            .collect()
+    }
+
+    #[test]
+    fn start_plays_first_step_at_once() {
+        let mut host = Host::new();
+        assert_eq!(host.play(&[512], 47000), straight_steps(0, 7));
+    }
+
+    #[test]
+    fn timing_does_not_depend_on_buffer_size() {
+        for buffer_sizes in [&[1][..], &[64], &[441], &[1000], &[37, 1024, 5, 333, 2048]] {
+            let mut host = Host::new();
+            assert_eq!(
+                host.play(buffer_sizes, 47000),
+                straight_steps(0, 7),
+                "buffer sizes {buffer_sizes:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn buffer_holds_several_steps() {
+        let mut host = Host::new();
+        assert_eq!(host.play(&[16384], 49152), straight_steps(0, 8));
+    }
+
+    #[test]
+    fn step_on_the_end_of_a_buffer_plays_once() {
+        let mut host = Host::new();
+        // each boundary falls on the end of one buffer and the start of the next
+        assert_eq!(host.play(&[6000], 18000), straight_steps(0, 2));
+        assert_eq!(host.play(&[6000], 24000), straight_steps(3, 3));
+    }
+
+    #[test]
+    fn start_within_a_step_waits_for_the_next() {
+        let mut host = Host::new();
+        host.seek(1000);
+        assert_eq!(host.play(&[512], 24000), straight_steps(1, 3));
+    }
+
+    #[test]
+    fn stop_and_start_again() {
+        let mut host = Host::new();
+        assert_eq!(host.play(&[512], 20000), straight_steps(0, 3));
+
+        // the notes are ended once when the transport stops
+        assert!(host.engine.stop());
+        assert!(!host.engine.stop());
+
+        host.seek(0);
+        assert_eq!(host.play(&[512], 13000), straight_steps(0, 2));
+
+        assert!(host.engine.stop());
+        host.seek(STEP_SAMPLES);
+        assert_eq!(host.play(&[512], 13000), straight_steps(1, 2));
+    }
+
+    #[test]
+    fn loop_starts_over() {
+        let mut host = Host::new();
+        assert_eq!(host.play(&[512], 24000), straight_steps(0, 3));
+
+        host.seek(0);
+        assert_eq!(host.play(&[512], 24000), straight_steps(0, 3));
+    }
+
+    #[test]
+    fn seek_waits_for_the_next_step() {
+        let mut host = Host::new();
+        assert_eq!(host.play(&[512], 5000), straight_steps(0, 0));
+
+        host.seek(33000);
+        assert_eq!(host.play(&[512], 47000), straight_steps(6, 7));
+    }
+
+    #[test]
+    fn seek_is_found_without_sample_positions() {
+        let mut host = Host::new();
+        host.has_samples = false;
+        assert_eq!(host.play(&[512], 5000), straight_steps(0, 0));
+
+        host.seek(30000);
+        assert_eq!(host.play(&[512], 37000), straight_steps(5, 6));
+    }
+
+    #[test]
+    fn step_missed_between_buffers_plays_at_buffer_start() {
+        let mut host = Host::new();
+        host.has_samples = false;
+        assert_eq!(host.play(&[5400, 512], 5912), straight_steps(0, 0));
+
+        // the host's beat position jumps past the step, but not far enough to count as moved
+        host.seek(6100);
+        assert_eq!(host.play(&[512], 12000), vec![(1, 6100)]);
+
+        let counters = host.engine.take_counters();
+        assert_eq!(counters.late_steps, 1);
+        assert_eq!(counters.missed_steps, 0);
+    }
+
+    #[test]
+    fn step_jumped_back_over_plays_once() {
+        let mut host = Host::new();
+        host.has_samples = false;
+        assert_eq!(host.play(&[512], 6100), straight_steps(0, 1));
+
+        // the host's beat position jumps back before the step, but not far enough to count as moved
+        host.seek(5950);
+        assert_eq!(host.play(&[512], 12500), straight_steps(2, 2));
+    }
+
+    #[test]
+    fn steps_follow_the_host_bar_lines() {
+        let mut host = Host::new();
+
+        // the bars start a tenth of a beat, 2400 samples, after beat 0
+        host.bar_offset_beats = Some(0.1);
+        let steps: Vec<_> = (0..=3)
+            .map(|step_count| (step_count, 2400 + step_count * STEP_SAMPLES))
+            .collect();
+        assert_eq!(host.play(&[512], 24000), steps);
+    }
+
+    #[test]
+    fn step_length_change_waits_for_the_bar_line() {
+        let mut host = Host::new();
+
+        // twice as many steps from the second bar, 96000 samples in. the bar line falls
+        // 4000 samples into a buffer that starts between two steps of the new grid.
+        host.step_change = Some((4.0, STEP_BEATS / 2.0));
+        let steps: Vec<_> = straight_steps(0, 15)
+            .into_iter()
+            .chain((32..=35).map(|step_count| (step_count, 96000 + (step_count - 32) * 3000)))
+            .collect();
+        assert_eq!(host.play(&[4600], 108000), steps);
+    }
+
+    #[test]
+    fn step_length_change_on_a_buffer_start() {
+        let mut host = Host::new();
+        host.step_change = Some((4.0, STEP_BEATS * 2.0));
+        let steps: Vec<_> = straight_steps(0, 15)
+            .into_iter()
+            .chain((8..=9).map(|step_count| (step_count, 96000 + (step_count - 8) * 12000)))
+            .collect();
+        assert_eq!(host.play(&[4000], 120000), steps);
+    }
+
+    #[test]
+    fn tempo_change_between_buffers() {
+        let mut host = Host::new();
+        assert_eq!(host.play(&[1000], 12000), straight_steps(0, 1));
+
+        // twice as fast, the steps half as long from beat 0.5 on
+        host.tempo = 240.0;
+        let steps: Vec<_> = (2..=5)
+            .map(|step_count| (step_count, 12000 + (step_count - 2) * STEP_SAMPLES / 2))
+            .collect();
+        assert_eq!(host.play(&[1000], 23000), steps);
+    }
+
+    #[test]
+    fn tempo_ramp() {
+        let mut host = Host::new();
+        let mut steps = Vec::new();
+        let mut expected = Vec::new();
+        let mut next_step = 0;
+
+        for buffer in 0..400 {
+            host.tempo = 120.0 + buffer as f64 * 0.5;
+            let buffer_samples = 480;
+
+            // the steps whose boundary the host's position passes during this buffer
+            let buffer_beats = buffer_samples as f64 / host.samples_per_beat();
+            while next_step as f64 * STEP_BEATS < host.pos_beats + buffer_beats {
+                let offset = (next_step as f64 * STEP_BEATS - host.pos_beats).max(0.0);
+                let timing = (offset * host.samples_per_beat()).round() as i64;
+                expected.push((next_step, host.pos_samples + timing));
+                next_step += 1;
+            }
+
+            steps.extend(host.process(buffer_samples));
+        }
+
+        assert!(expected.len() > 40);
+        assert_eq!(steps, expected);
+    }
+}
@@ -0,0 +1,34 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// what a pattern does once it's played through the set number of times
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FollowAction {
+    // keeps looping
+    #[id = "off"]
+    #[name = "Off"]
+    Off,
+
+    #[id = "next"]
+    #[name = "Next"]
+    Next,
+
+    #[id = "previous"]
+    #[name = "Previous"]
+    Previous,
+
+    // any other pattern that isn't empty
+    #[id = "random"]
+    #[name = "Random"]
+    Random,
+
+    // the pattern's follow pattern
+    #[id = "pattern"]
+    #[name = "Pattern"]
+    Pattern,
+
+    // silence until another pattern is selected or playback starts again
+    #[id = "stop"]
+    #[name = "Stop"]
+    Stop,
+}
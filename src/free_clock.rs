@@ -0,0 +1,25 @@
+// a clock of the plugin's own running at a set tempo, so the tracks can play
+// without the host's transport
+#[derive(Default)]
+pub struct FreeClock {
+    // position of the start of the next buffer
+    pos_beats: f64,
+    pos_samples: i64,
+}
+
+impl FreeClock {
+    // returns the position of the start of this buffer in beats and samples,
+    // and moves the clock on to the start of the next buffer
+    pub fn advance(&mut self, buffer_samples: usize, samples_per_beat: f64) -> (f64, i64) {
+        let pos = (self.pos_beats, self.pos_samples);
+        self.pos_beats += buffer_samples as f64 / samples_per_beat;
+        self.pos_samples += buffer_samples as i64;
+        pos
+    }
+
+    // start again from the beginning of the song
+    pub fn reset(&mut self) {
+        self.pos_beats = 0.0;
+        self.pos_samples = 0;
+    }
+}
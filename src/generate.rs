@@ -0,0 +1,83 @@
+use crate::pattern::{Step, Track};
+use crate::rng::Rng;
+use crate::scale::PitchClasses;
+
+// lowest note random notes are picked from, the octaves above it are used too
+const LOWEST_NOTE: u8 = 60;
+
+// random velocities and gates stay within these ranges so every note is heard
+const MIN_VELOCITY: f32 = 0.5;
+const MIN_GATE: f32 = 0.1;
+
+// fill the steps of a track with random notes of the scale. density is the
+// chance of each step playing, the track keeps its length and rate.
+pub fn randomize(
+    track: &Track,
+    rng: &mut Rng,
+    scale: PitchClasses,
+    root: u8,
+    octaves: u8,
+    density: f32,
+) -> Track {
+    let mut track = track.clone();
+
+    for step in &mut track.steps[..track.len] {
+        let octave = (rng.next_u64() % octaves.max(1) as u64) as u8;
+        let degree_index = (rng.next_u64() % scale.num_degrees() as u64) as usize;
+        let degree = scale.degrees().nth(degree_index).unwrap_or(0);
+        let note = LOWEST_NOTE + octave * 12 + (root + degree) % 12;
+
+        *step = Step {
+            enabled: rng.next_f32() < density,
+            note: note.min(127),
+            velocity: MIN_VELOCITY + rng.next_f32() * (1.0 - MIN_VELOCITY),
+            gate: MIN_GATE + rng.next_f32() * (1.0 - MIN_GATE),
+            ..Step::default()
+        };
+    }
+    track
+}
+
+// furthest a mutation moves a step's velocity either way
+const MUTATE_VELOCITY: f32 = 0.2;
+
+// make small random edits to the steps of a track, amount is the chance of each
+// step being edited. run over and over, the track slowly evolves.
+pub fn mutate(track: &Track, rng: &mut Rng, scale: PitchClasses, root: u8, amount: f32) -> Track {
+    let mut track = track.clone();
+
+    for step in &mut track.steps[..track.len] {
+        if rng.next_f32() >= amount {
+            continue;
+        }
+
+        match rng.next_u64() % 3 {
+            0 => {
+                let up = rng.next_u64() % 2 == 0;
+                step.note = scale_step(step.note, up, scale, root);
+            }
+            1 => step.enabled = !step.enabled,
+            _ => {
+                let offset = (rng.next_f32() * 2.0 - 1.0) * MUTATE_VELOCITY;
+                step.velocity = (step.velocity + offset).clamp(MIN_VELOCITY / 2.0, 1.0);
+            }
+        }
+    }
+    track
+}
+
+// next note of the scale above or below the given note, staying within midi range
+fn scale_step(note: u8, up: bool, scale: PitchClasses, root: u8) -> u8 {
+    let mut next = note;
+    for _ in 0..12 {
+        next = match (up, next) {
+            (true, 127) | (false, 0) => return note,
+            (true, _) => next + 1,
+            (false, _) => next - 1,
+        };
+        if scale.contains(next, root) {
+            return next;
+        }
+    }
+    note
+}
@@ -0,0 +1,95 @@
+use crate::swing;
+
+// fractions of a step closer than this to a whole step are treated as whole
+const EPSILON: f64 = 1e-9;
+
+// the steps of a track laid out over the bars of the song. the grid starts over
+// at every bar, so in time signatures that don't divide into whole steps the last
+// step of each bar is cut short instead of drifting across the bar line.
+// step counts run on from bar to bar, so a bar of steps_per_bar steps holds
+// the step counts starting at bar * steps_per_bar.
+pub struct Grid {
+    // duration of a straight step in beats
+    step_beats: f64,
+
+    // duration of a bar in beats
+    bar_beats: f64,
+
+    // number of steps starting in each bar
+    steps_per_bar: i64,
+
+    // fraction of a step that swing delays even numbered steps by
+    swing_delay: f64,
+
+    // a bar the host reported the position of, the other bars are measured from it
+    bar_start_beats: f64,
+    bar_number: i64,
+}
+
+impl Grid {
+    pub fn new(
+        step_beats: f64,
+        bar_beats: f64,
+        swing_delay: f64,
+        bar_start_beats: f64,
+        bar_number: i64,
+    ) -> Self {
+        // triplet steps don't divide a bar exactly in binary
+        let steps = bar_beats / step_beats;
+        let steps_per_bar = if (steps - steps.round()).abs() < EPSILON * steps {
+            steps.round()
+        } else {
+            steps.ceil()
+        };
+
+        Self {
+            step_beats,
+            bar_beats,
+            steps_per_bar: (steps_per_bar as i64).max(1),
+            swing_delay,
+            bar_start_beats,
+            bar_number,
+        }
+    }
+
+    // bar containing the given position in beats
+    fn pos_bar(&self, pos_beats: f64) -> i64 {
+        let bars = (pos_beats - self.bar_start_beats) / self.bar_beats;
+        self.bar_number + (bars + EPSILON).floor() as i64
+    }
+
+    // position in beats of the start of the given bar
+    fn bar_pos_beats(&self, bar: i64) -> f64 {
+        self.bar_start_beats + (bar - self.bar_number) as f64 * self.bar_beats
+    }
+
+    // position in swung steps since the start of the song, every step starts on a whole number
+    pub fn pos_steps(&self, pos_beats: f64) -> f64 {
+        let bar = self.pos_bar(pos_beats);
+        let bar_pos_steps = (pos_beats - self.bar_pos_beats(bar)).max(0.0) / self.step_beats;
+        let bar_pos_steps = swing::swung_pos(bar_pos_steps, self.swing_delay);
+
+        // the last step of a bar that's cut short runs up to the next bar
+        let bar_pos_steps = bar_pos_steps.min(self.steps_per_bar as f64 - EPSILON);
+
+        (bar * self.steps_per_bar) as f64 + bar_pos_steps
+    }
+
+    // position in beats of the start of the given step
+    pub fn step_pos_beats(&self, step_count: i64) -> f64 {
+        let bar = self.bar(step_count);
+        let bar_step = step_count.rem_euclid(self.steps_per_bar);
+        let bar_pos_steps = swing::straight_pos(bar_step as f64, self.swing_delay);
+        self.bar_pos_beats(bar) + bar_pos_steps * self.step_beats
+    }
+
+    // duration of the given step in beats
+    pub fn step_len_beats(&self, step_count: i64) -> f64 {
+        self.step_pos_beats(step_count + 1) - self.step_pos_beats(step_count)
+    }
+
+    // bar containing the start of the given step
+    pub fn bar(&self, step_count: i64) -> i64 {
+        step_count.div_euclid(self.steps_per_bar)
+    }
+}
@@ -0,0 +1,157 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// longest cycle of steps a groove template can repeat over
+pub const MAX_GROOVE_STEPS: usize = 16;
+
+// timing and velocity offsets played on top of a pattern, repeating every len steps.
+// timing is a fraction of a step late, velocity is added to the step's velocity.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GrooveTemplate {
+    pub len: usize,
+    pub timing: [f32; MAX_GROOVE_STEPS],
+    pub velocity: [f32; MAX_GROOVE_STEPS],
+}
+
+impl GrooveTemplate {
+    // a template repeating the given offsets, the rest of the steps are left as they are
+    const fn new(len: usize, timing: [f32; 4], velocity: [f32; 4]) -> Self {
+        let mut template = Self {
+            len,
+            timing: [0.0; MAX_GROOVE_STEPS],
+            velocity: [0.0; MAX_GROOVE_STEPS],
+        };
+        let mut index = 0;
+        while index < 4 {
+            template.timing[index] = timing[index];
+            template.velocity[index] = velocity[index];
+            index += 1;
+        }
+        template
+    }
+
+    // mpc style swing, the second step of each pair lands at the given
+    // percentage of the pair's length
+    const fn swing(percent: f32) -> Self {
+        let delay = percent / 50.0 - 1.0;
+        Self::new(2, [0.0, delay, 0.0, 0.0], [0.0; 4])
+    }
+
+    // fraction of a step the given step is played late by
+    pub fn timing(&self, step_count: i64) -> f32 {
+        self.timing[self.index(step_count)]
+    }
+
+    // amount added to the given step's velocity
+    pub fn velocity(&self, step_count: i64) -> f32 {
+        self.velocity[self.index(step_count)]
+    }
+
+    fn index(&self, step_count: i64) -> usize {
+        // a user groove restored from saved state may claim more steps than it holds
+        step_count.rem_euclid(self.len.clamp(1, MAX_GROOVE_STEPS) as i64) as usize
+    }
+}
+
+// the groove templates that come with the plugin
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Groove {
+    #[id = "off"]
+    #[name = "Off"]
+    Off,
+
+    #[id = "mpc_54"]
+    #[name = "MPC 54%"]
+    Mpc54,
+
+    #[id = "mpc_58"]
+    #[name = "MPC 58%"]
+    Mpc58,
+
+    #[id = "mpc_62"]
+    #[name = "MPC 62%"]
+    Mpc62,
+
+    #[id = "mpc_66"]
+    #[name = "MPC 66%"]
+    Mpc66,
+
+    #[id = "mpc_71"]
+    #[name = "MPC 71%"]
+    Mpc71,
+
+    // straight timing with louder downbeats and softer offbeats
+    #[id = "drum_machine"]
+    #[name = "Drum Machine Accent"]
+    DrumMachine,
+
+    // light shuffle with a bounce on the offbeats
+    #[id = "house"]
+    #[name = "House Shuffle"]
+    House,
+
+    // heavily dragged offbeats, played soft
+    #[id = "lazy"]
+    #[name = "Lazy Hip Hop"]
+    Lazy,
+
+    // extracted from a midi file
+    #[id = "user"]
+    #[name = "User"]
+    User,
+}
+
+impl Groove {
+    pub const OFF: GrooveTemplate = GrooveTemplate::new(1, [0.0; 4], [0.0; 4]);
+    const MPC_54: GrooveTemplate = GrooveTemplate::swing(54.0);
+    const MPC_58: GrooveTemplate = GrooveTemplate::swing(58.0);
+    const MPC_62: GrooveTemplate = GrooveTemplate::swing(62.0);
+    const MPC_66: GrooveTemplate = GrooveTemplate::swing(66.0);
+    const MPC_71: GrooveTemplate = GrooveTemplate::swing(71.0);
+    const DRUM_MACHINE: GrooveTemplate = GrooveTemplate::new(4, [0.0; 4], [0.15, -0.1, 0.05, -0.1]);
+    const HOUSE: GrooveTemplate =
+        GrooveTemplate::new(4, [0.0, 0.1, 0.0, 0.12], [0.1, -0.05, 0.0, 0.05]);
+    const LAZY: GrooveTemplate =
+        GrooveTemplate::new(4, [0.0, 0.3, 0.05, 0.35], [0.0, -0.15, -0.05, -0.2]);
+
+    // the template played for the groove, the user groove is the one extracted last
+    pub fn template(self, user: &GrooveTemplate) -> GrooveTemplate {
+        match self {
+            Self::User => *user,
+            Self::Off => Self::OFF,
+            Self::Mpc54 => Self::MPC_54,
+            Self::Mpc58 => Self::MPC_58,
+            Self::Mpc62 => Self::MPC_62,
+            Self::Mpc66 => Self::MPC_66,
+            Self::Mpc71 => Self::MPC_71,
+            Self::DrumMachine => Self::DRUM_MACHINE,
+            Self::House => Self::HOUSE,
+            Self::Lazy => Self::LAZY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_wraps_around_len() {
+        let template = Groove::HOUSE;
+        assert_eq!(template.index(0), 0);
+        assert_eq!(template.index(5), 1);
+        assert_eq!(template.index(-1), 3);
+    }
+
+    #[test]
+    fn index_stays_within_template() {
+        let mut template = Groove::OFF;
+        template.len = 40;
+        assert_eq!(template.index(17), 1);
+        assert_eq!(template.index(-1), MAX_GROOVE_STEPS - 1);
+        assert_eq!(template.timing(39), 0.0);
+
+        template.len = 0;
+        assert_eq!(template.index(7), 0);
+    }
+}
@@ -0,0 +1,86 @@
+use nih_plug::prelude::*;
+use std::sync::Arc;
+
+// the notes of the midi input one of the plugin's functions responds to
+#[derive(Params)]
+pub struct InputFilterParams {
+    // 0 takes notes on any channel
+    #[id = "channel"]
+    pub channel: IntParam,
+
+    #[id = "low"]
+    pub low: IntParam,
+
+    #[id = "high"]
+    pub high: IntParam,
+}
+
+impl InputFilterParams {
+    pub fn new(function: &str) -> Self {
+        Self {
+            channel: IntParam::new(
+                format!("{function} Channel"),
+                0,
+                IntRange::Linear { min: 0, max: 16 },
+            )
+            .with_value_to_string(Arc::new(|value| match value {
+                0 => String::from("Any"),
+                _ => value.to_string(),
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                if string.eq_ignore_ascii_case("any") {
+                    Some(0)
+                } else {
+                    string.parse().ok()
+                }
+            })),
+            low: IntParam::new(
+                format!("{function} Low Note"),
+                0,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            high: IntParam::new(
+                format!("{function} High Note"),
+                127,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+        }
+    }
+
+    // the note played on the zero based channel is let through to the function
+    pub fn accepts(&self, channel: u8, note: u8) -> bool {
+        let channel_matches = match self.channel.value() {
+            0 => true,
+            filter_channel => filter_channel == channel as i32 + 1,
+        };
+        let note = note as i32;
+        channel_matches && note >= self.low.value() && note <= self.high.value()
+    }
+}
+
+// side of the split point the keys controlling the sequencer are on,
+// the keys on the other side are played through
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum SplitZone {
+    #[id = "below"]
+    #[name = "Below"]
+    Below,
+
+    #[id = "above"]
+    #[name = "Above"]
+    Above,
+}
+
+impl SplitZone {
+    // the key is on the control side, the split point itself belongs to the upper side
+    pub fn controls(self, split: u8, note: u8) -> bool {
+        match self {
+            Self::Below => note < split,
+            Self::Above => note >= split,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use nih_plug::prelude::*;
+
+// boundary a newly selected pattern waits for before it starts playing
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum LaunchQuantize {
+    // the next step of each track
+    #[id = "step"]
+    #[name = "Step"]
+    Step,
+
+    #[id = "bar"]
+    #[name = "1 Bar"]
+    Bar,
+
+    #[id = "two_bars"]
+    #[name = "2 Bars"]
+    TwoBars,
+
+    #[id = "four_bars"]
+    #[name = "4 Bars"]
+    FourBars,
+}
+
+impl LaunchQuantize {
+    // first bar after the given one that a pattern can be launched at,
+    // none when patterns are launched on the next step
+    pub fn launch_bar(self, bar: i64) -> Option<i64> {
+        let bars = match self {
+            Self::Step => return None,
+            Self::Bar => 1,
+            Self::TwoBars => 2,
+            Self::FourBars => 4,
+        };
+        let next_bar = bar + 1;
+        Some(next_bar + (bars - next_bar.rem_euclid(bars)) % bars)
+    }
+}
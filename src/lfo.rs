@@ -0,0 +1,153 @@
+use nih_plug::prelude::*;
+use std::f64::consts::TAU;
+
+use crate::rate::Rate;
+use crate::rng::Rng;
+
+// shape of one cycle of the lfo
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum LfoShape {
+    #[id = "sine"]
+    #[name = "Sine"]
+    Sine,
+
+    #[id = "triangle"]
+    #[name = "Triangle"]
+    Triangle,
+
+    #[id = "saw"]
+    #[name = "Saw"]
+    Saw,
+
+    #[id = "square"]
+    #[name = "Square"]
+    Square,
+
+    // a new random value at the start of every cycle
+    #[id = "random"]
+    #[name = "Random"]
+    Random,
+}
+
+impl LfoShape {
+    // value between 0 and 1 at the given position in beats
+    fn value(self, pos_beats: f64, cycle_beats: f64) -> f64 {
+        let cycles = pos_beats / cycle_beats;
+        let phase = cycles - cycles.floor();
+        match self {
+            Self::Sine => 0.5 - 0.5 * (phase * TAU).cos(),
+            Self::Triangle => 1.0 - (2.0 * phase - 1.0).abs(),
+            Self::Saw => phase,
+            Self::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            // the same cycle of the song always gets the same value
+            Self::Random => Rng::for_step(0, 0, cycles.floor() as i64).next_f32() as f64,
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct LfoParams {
+    #[id = "lfo_on"]
+    pub enabled: BoolParam,
+
+    #[id = "lfo_shape"]
+    pub shape: EnumParam<LfoShape>,
+
+    // duration of a cycle
+    #[id = "lfo_rate"]
+    pub rate: EnumParam<Rate>,
+
+    #[id = "lfo_cc"]
+    pub cc: IntParam,
+
+    #[id = "lfo_channel"]
+    pub channel: IntParam,
+
+    // range the lfo sweeps over around its center
+    #[id = "lfo_depth"]
+    pub depth: FloatParam,
+
+    #[id = "lfo_center"]
+    pub center: FloatParam,
+
+    // controller messages sent per beat, fewer spares the midi output
+    #[id = "lfo_updates"]
+    pub updates_per_beat: IntParam,
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("LFO", false),
+            shape: EnumParam::new("LFO Shape", LfoShape::Sine),
+            rate: EnumParam::new("LFO Rate", Rate::Whole),
+            cc: IntParam::new("LFO CC", 1, IntRange::Linear { min: 0, max: 127 }),
+            channel: IntParam::new("LFO Channel", 1, IntRange::Linear { min: 1, max: 16 }),
+            depth: FloatParam::new("LFO Depth", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            center: FloatParam::new("LFO Center", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            updates_per_beat: IntParam::new(
+                "LFO Updates",
+                24,
+                IntRange::Linear { min: 1, max: 96 },
+            )
+            .with_unit(" /beat"),
+        }
+    }
+}
+
+// a controller swept in time with the song. updates fall on fixed subdivisions
+// of the beat, so the lfo is in the same phase wherever playback starts.
+#[derive(Default)]
+pub struct Lfo {
+    // controller value last sent, values that don't change aren't sent again
+    last_value: Option<u8>,
+}
+
+impl Lfo {
+    // controller value at the given position in beats, or none if it's the value last sent
+    pub fn value(&mut self, params: &LfoParams, pos_beats: f64) -> Option<u8> {
+        let shape = params
+            .shape
+            .value()
+            .value(pos_beats, params.rate.value().beats());
+        let depth = params.depth.value() as f64;
+        let value = params.center.value() as f64 + depth * (shape - 0.5);
+        let value = (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+
+        if self.last_value.replace(value) == Some(value) {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    // send the next value whatever the last one was, e.g. after the transport stops
+    pub fn reset(&mut self) {
+        self.last_value = None;
+    }
+}
+
+// distance in beats from the given position to each update before the end of the buffer
+pub fn updates(
+    updates_per_beat: i32,
+    pos_beats: f64,
+    buffer_beats: f64,
+) -> impl Iterator<Item = f64> {
+    let updates_per_beat = updates_per_beat as f64;
+    let first_update = (pos_beats * updates_per_beat).ceil() as i64;
+    (first_update..)
+        .map(move |update| update as f64 / updates_per_beat - pos_beats)
+        .take_while(move |&beats| beats < buffer_beats)
+}
@@ -1,49 +1,2981 @@
 use nih_plug::prelude::*;
-use std::sync::Arc;
+use nih_plug_egui::EguiState;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
 
+#[cfg(feature = "debug-log")]
 const MILLISECONDS: &[time::format_description::FormatItem] =
     time::macros::format_description!("[subsecond digits:3]");
 
+// messages logged from the audio thread are held back when there are too many of them
+#[cfg(feature = "debug-log")]
+static LOG_LIMITER: rate_limit::RateLimiter = rate_limit::RateLimiter::new();
+
+#[cfg(feature = "debug-log")]
+macro_rules! nih_log {
+    ($($args:tt)*) => {{
+        if let Some(dropped) = $crate::LOG_LIMITER.allow() {
+            let ms = time::OffsetDateTime::now_utc().format($crate::MILLISECONDS).unwrap_or("xxx".to_string());
+            if dropped > 0 {
+                nih_plug::prelude::nih_log!("{ms} {dropped} messages dropped");
+            }
+            let ms_msg = format!("{} {}", ms, format_args!($($args)*));
+            nih_plug::prelude::nih_log!("{ms_msg}");
+        }
+    }};
+}
+
+// without the debug-log feature nothing is formatted or logged,
+// the arguments are still checked so both builds see the same code
+#[cfg(not(feature = "debug-log"))]
 macro_rules! nih_log {
-    ($($args:tt)*) => (
-        let ms = time::OffsetDateTime::now_utc().format(MILLISECONDS).unwrap_or("xxx".to_string());
-        let ms_msg = format!("{} {}", ms, format_args!($($args)*));
-        nih_plug::prelude::nih_log!("{ms_msg}");
-    );
+    ($($args:tt)*) => {{
+        if false {
+            let _ = format_args!($($args)*);
+        }
+    }};
+}
+
+mod actions;
+mod arp;
+mod chain;
+mod chord;
+mod condition;
+mod diagnostics;
+mod editor;
+// public for the benchmarks
+pub mod engine;
+mod follow;
+mod free_clock;
+mod generate;
+mod grid;
+mod groove;
+mod input_filter;
+mod launch;
+mod lfo;
+mod midi_clock;
+mod midi_file;
+mod midi_learn;
+mod morph;
+mod note_names;
+mod pattern;
+mod preset;
+mod rate;
+#[cfg(feature = "debug-log")]
+mod rate_limit;
+mod record;
+mod rng;
+mod scale;
+mod scheduler;
+mod sounding;
+mod speed;
+mod strum;
+mod swing;
+mod sysex;
+mod trigger;
+mod variation;
+mod velocity;
+
+use actions::{ActionParams, PatternActions};
+use arp::{ArpMode, HeldKeys};
+use chain::{ChainEntry, CHAIN_LEN};
+use chord::ChordType;
+use condition::TrigCondition;
+use diagnostics::{Diagnostic, Diagnostics};
+use engine::{Clock, Engine, StepStart, TransportSnapshot};
+use follow::FollowAction;
+use free_clock::FreeClock;
+use grid::Grid;
+use groove::{Groove, GrooveTemplate};
+use input_filter::{InputFilterParams, SplitZone};
+use launch::LaunchQuantize;
+use lfo::{Lfo, LfoParams};
+use midi_clock::{ClockMessage, MidiClock};
+use midi_learn::{CcMap, LearnTarget, MidiLearn};
+use note_names::NoteNames;
+use pattern::{
+    Pattern, PoolNote, Step, Track, MAX_CHORD_NOTES, MAX_POOL_NOTES, MAX_STEPS, NUM_CC_LANES,
+    NUM_PATTERNS, NUM_TRACKS,
+};
+use rate::Rate;
+use record::Recorder;
+use rng::Rng;
+use scale::{PitchClasses, Scale};
+use scheduler::{NoteKind, NoteScheduler, ScheduledNote};
+use sounding::SoundingNotes;
+use speed::Speed;
+use strum::StrumDirection;
+use sysex::SysEx;
+use variation::Variation;
+use velocity::VelocityCurve;
+
+// value of a parameter with or without the modulation a clap host applies on top of it
+fn param_value<P: Param>(param: &P, modulated: bool) -> P::Plain {
+    if modulated {
+        param.modulated_plain_value()
+    } else {
+        param.unmodulated_plain_value()
+    }
+}
+
+// shows the given value of an int parameter as "Off" and reads "off" back as it
+fn with_off(
+    param: IntParam,
+    off: i32,
+    value_to_string: Arc<dyn Fn(i32) -> String + Send + Sync>,
+    string_to_value: Arc<dyn Fn(&str) -> Option<i32> + Send + Sync>,
+) -> IntParam {
+    param
+        .with_value_to_string(Arc::new(move |value| {
+            if value == off {
+                String::from("Off")
+            } else {
+                value_to_string(value)
+            }
+        }))
+        .with_string_to_value(Arc::new(move |string| {
+            if string.eq_ignore_ascii_case("off") {
+                Some(off)
+            } else {
+                string_to_value(string)
+            }
+        }))
+}
+
+// -1 is off, other values are shown as numbers
+fn off_or_value(param: IntParam) -> IntParam {
+    with_off(
+        param,
+        -1,
+        Arc::new(|value| value.to_string()),
+        Arc::new(|string| string.parse().ok()),
+    )
+}
+
+// -1 is off, other values are shown as note names
+fn off_or_note(param: IntParam) -> IntParam {
+    with_off(
+        param,
+        -1,
+        formatters::v2s_i32_note_formatter(),
+        formatters::s2v_i32_note_formatter(),
+    )
+}
+
+// 0 is off, other values are pattern numbers
+fn off_or_pattern(param: IntParam) -> IntParam {
+    with_off(
+        param,
+        0,
+        Arc::new(|value| value.to_string()),
+        Arc::new(|string| string.parse().ok()),
+    )
+}
+
+#[derive(Params)]
+struct StepParams {
+    #[id = "on"]
+    enabled: BoolParam,
+
+    #[id = "note"]
+    note: IntParam,
+
+    #[id = "vel"]
+    velocity: FloatParam,
+
+    #[id = "vel_range"]
+    velocity_range: FloatParam,
+
+    #[id = "gate"]
+    gate: FloatParam,
+
+    #[id = "accent"]
+    accent: BoolParam,
+
+    #[id = "prob"]
+    probability: FloatParam,
+
+    #[id = "variation"]
+    variation: FloatParam,
+
+    #[id = "cond"]
+    condition: EnumParam<TrigCondition>,
+
+    #[id = "ratchet"]
+    ratchets: IntParam,
+
+    #[id = "tie"]
+    tie: BoolParam,
+
+    #[id = "slide"]
+    slide: BoolParam,
+
+    #[id = "offset"]
+    offset: FloatParam,
+
+    #[id = "chord"]
+    chord_type: EnumParam<ChordType>,
+
+    #[nested(array, group = "Chord")]
+    chord_notes: [ChordNoteParams; MAX_CHORD_NOTES - 1],
+
+    #[nested(array, group = "Pool")]
+    pool: [PoolNoteParams; MAX_POOL_NOTES],
+
+    #[nested(array, group = "CC")]
+    cc: [StepCcParams; NUM_CC_LANES],
+
+    #[id = "pressure"]
+    pressure: IntParam,
+
+    #[id = "volume"]
+    volume: FloatParam,
+
+    #[id = "pan"]
+    pan: FloatParam,
+
+    #[id = "tuning"]
+    tuning: FloatParam,
+}
+
+impl StepParams {
+    fn new(index: usize) -> Self {
+        Self {
+            enabled: BoolParam::new(format!("Step {} On", index + 1), Step::DEFAULT_ENABLED),
+            note: IntParam::new(
+                format!("Step {} Note", index + 1),
+                Step::DEFAULT_NOTE as i32,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            velocity: FloatParam::new(
+                format!("Step {} Velocity", index + 1),
+                Step::DEFAULT_VELOCITY,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_range: FloatParam::new(
+                format!("Step {} Velocity Range", index + 1),
+                Step::DEFAULT_VELOCITY_RANGE,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            gate: FloatParam::new(
+                format!("Step {} Gate", index + 1),
+                Step::DEFAULT_GATE,
+                FloatRange::Linear {
+                    min: Step::MIN_GATE,
+                    max: 1.0,
+                },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            accent: BoolParam::new(format!("Step {} Accent", index + 1), Step::DEFAULT_ACCENT),
+            probability: FloatParam::new(
+                format!("Step {} Probability", index + 1),
+                Step::DEFAULT_PROBABILITY,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            variation: FloatParam::new(
+                format!("Step {} Variation", index + 1),
+                Step::DEFAULT_VARIATION,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            condition: EnumParam::new(
+                format!("Step {} Condition", index + 1),
+                Step::DEFAULT_CONDITION,
+            ),
+            ratchets: IntParam::new(
+                format!("Step {} Ratchets", index + 1),
+                Step::DEFAULT_RATCHETS as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: Step::MAX_RATCHETS as i32,
+                },
+            ),
+            tie: BoolParam::new(format!("Step {} Tie", index + 1), Step::DEFAULT_TIE),
+            slide: BoolParam::new(format!("Step {} Slide", index + 1), Step::DEFAULT_SLIDE),
+            offset: FloatParam::new(
+                format!("Step {} Offset", index + 1),
+                Step::DEFAULT_OFFSET,
+                FloatRange::Linear {
+                    min: -Step::MAX_OFFSET,
+                    max: Step::MAX_OFFSET,
+                },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            chord_type: EnumParam::new(
+                format!("Step {} Chord", index + 1),
+                Step::DEFAULT_CHORD_TYPE,
+            ),
+            chord_notes: std::array::from_fn(|chord_index| {
+                ChordNoteParams::new(index, chord_index)
+            }),
+            pool: std::array::from_fn(|pool_index| PoolNoteParams::new(index, pool_index)),
+            cc: std::array::from_fn(|lane| StepCcParams::new(index, lane)),
+            // -1 sends no aftertouch
+            pressure: off_or_value(IntParam::new(
+                format!("Step {} Pressure", index + 1),
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+            volume: FloatParam::new(
+                format!("Step {} Volume", index + 1),
+                Step::DEFAULT_VOLUME,
+                FloatRange::Linear {
+                    min: Step::MIN_VOLUME,
+                    max: Step::MAX_VOLUME,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            pan: FloatParam::new(
+                format!("Step {} Pan", index + 1),
+                Step::DEFAULT_PAN,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+            tuning: FloatParam::new(
+                format!("Step {} Tuning", index + 1),
+                Step::DEFAULT_TUNING,
+                FloatRange::Linear {
+                    min: -Step::MAX_TUNING,
+                    max: Step::MAX_TUNING,
+                },
+            )
+            .with_unit(" st")
+            .with_step_size(0.01),
+        }
+    }
+}
+
+impl StepParams {
+    // the step the parameters are set to, with or without the host's modulation
+    fn step(&self, modulated: bool) -> Step {
+        Step {
+            enabled: param_value(&self.enabled, modulated),
+            note: param_value(&self.note, modulated) as u8,
+            velocity: param_value(&self.velocity, modulated),
+            velocity_range: param_value(&self.velocity_range, modulated),
+            gate: param_value(&self.gate, modulated),
+            accent: param_value(&self.accent, modulated),
+            probability: param_value(&self.probability, modulated),
+            variation: param_value(&self.variation, modulated),
+            condition: param_value(&self.condition, modulated),
+            ratchets: param_value(&self.ratchets, modulated) as u8,
+            tie: param_value(&self.tie, modulated),
+            slide: param_value(&self.slide, modulated),
+            offset: param_value(&self.offset, modulated),
+            chord_type: param_value(&self.chord_type, modulated),
+            chord_notes: std::array::from_fn(|index| self.chord_notes[index].note(modulated)),
+            pool: std::array::from_fn(|index| self.pool[index].pool_note(modulated)),
+            cc: std::array::from_fn(|lane| self.cc[lane].value(modulated)),
+            pressure: u8::try_from(param_value(&self.pressure, modulated)).ok(),
+            volume: param_value(&self.volume, modulated),
+            pan: param_value(&self.pan, modulated),
+            tuning: param_value(&self.tuning, modulated),
+        }
+    }
+}
+
+#[derive(Params)]
+struct ChordNoteParams {
+    #[id = "chord_note"]
+    note: IntParam,
+}
+
+impl ChordNoteParams {
+    fn new(step_index: usize, index: usize) -> Self {
+        Self {
+            // -1 leaves the chord note out, the step's own note is the first note of the chord
+            note: off_or_note(IntParam::new(
+                format!("Step {} Chord Note {}", step_index + 1, index + 2),
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+        }
+    }
+
+    fn note(&self, modulated: bool) -> Option<u8> {
+        u8::try_from(param_value(&self.note, modulated)).ok()
+    }
+}
+
+#[derive(Params)]
+struct PoolNoteParams {
+    #[id = "pool_note"]
+    note: IntParam,
+
+    #[id = "pool_weight"]
+    weight: FloatParam,
+}
+
+impl PoolNoteParams {
+    fn new(step_index: usize, index: usize) -> Self {
+        Self {
+            // -1 leaves the pool note out
+            note: off_or_note(IntParam::new(
+                format!("Step {} Pool Note {}", step_index + 1, index + 1),
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+            weight: FloatParam::new(
+                format!("Step {} Pool Weight {}", step_index + 1, index + 1),
+                PoolNote::DEFAULT_WEIGHT,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+
+    fn pool_note(&self, modulated: bool) -> Option<PoolNote> {
+        let note = u8::try_from(param_value(&self.note, modulated)).ok()?;
+        Some(PoolNote {
+            note,
+            weight: param_value(&self.weight, modulated),
+        })
+    }
+}
+
+#[derive(Params)]
+struct StepCcParams {
+    #[id = "cc_value"]
+    value: IntParam,
+}
+
+impl StepCcParams {
+    fn new(step_index: usize, lane: usize) -> Self {
+        Self {
+            // -1 sends nothing on the step
+            value: off_or_value(IntParam::new(
+                format!("Step {} CC {}", step_index + 1, lane + 1),
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+        }
+    }
+
+    fn value(&self, modulated: bool) -> Option<u8> {
+        u8::try_from(param_value(&self.value, modulated)).ok()
+    }
+}
+
+#[derive(Params)]
+struct ChainParams {
+    #[id = "chain_pattern"]
+    pattern: IntParam,
+
+    #[id = "chain_bars"]
+    bars: IntParam,
+}
+
+impl ChainParams {
+    fn new(index: usize) -> Self {
+        Self {
+            // 0 ends the chain
+            pattern: off_or_pattern(IntParam::new(
+                format!("Chain {} Pattern", index + 1),
+                if index == 0 { 1 } else { 0 },
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_PATTERNS as i32,
+                },
+            )),
+            bars: IntParam::new(
+                format!("Chain {} Bars", index + 1),
+                1,
+                IntRange::Linear { min: 1, max: 64 },
+            ),
+        }
+    }
+
+    fn entry(&self) -> ChainEntry {
+        let pattern = self.pattern.value() as usize;
+        ChainEntry {
+            pattern: pattern.checked_sub(1),
+            bars: self.bars.value() as u32,
+        }
+    }
+}
+
+#[derive(Params)]
+struct TrackParams {
+    // muted tracks keep their place in the song but play nothing
+    #[id = "mute"]
+    mute: BoolParam,
+
+    // midi channel the track's notes are sent on, 0 follows the output channel
+    #[id = "channel"]
+    channel: IntParam,
+
+    // play the keys held on the midi input in the rhythm of the track's steps
+    #[id = "arp"]
+    arp_mode: EnumParam<ArpMode>,
+
+    // note the track plays in drum mode
+    #[id = "drum_note"]
+    drum_note: IntParam,
+
+    // controller each of the track's cc lanes is sent to
+    #[nested(array, group = "CC Lane")]
+    cc_lanes: [CcLaneParams; NUM_CC_LANES],
+}
+
+#[derive(Params)]
+struct CcLaneParams {
+    #[id = "cc_number"]
+    number: IntParam,
+}
+
+impl CcLaneParams {
+    // filter cutoff and resonance in the general midi sound controller layout
+    const DEFAULT_NUMBERS: [i32; NUM_CC_LANES] = [74, 71];
+
+    fn new(track_index: usize, lane: usize) -> Self {
+        Self {
+            number: IntParam::new(
+                format!("Track {} CC Lane {}", track_index + 1, lane + 1),
+                Self::DEFAULT_NUMBERS[lane],
+                IntRange::Linear { min: 0, max: 127 },
+            ),
+        }
+    }
+}
+
+impl TrackParams {
+    // kick, snare, closed and open hi-hat in the general midi drum map
+    const GM_DRUM_NOTES: [i32; NUM_TRACKS] = [36, 38, 42, 46];
+
+    fn new(index: usize) -> Self {
+        Self {
+            mute: BoolParam::new(format!("Track {} Mute", index + 1), false),
+            channel: IntParam::new(
+                format!("Track {} Channel", index + 1),
+                0,
+                IntRange::Linear { min: 0, max: 16 },
+            )
+            .with_value_to_string(Arc::new(|value| match value {
+                0 => String::from("Global"),
+                _ => value.to_string(),
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                if string.eq_ignore_ascii_case("global") {
+                    Some(0)
+                } else {
+                    string.parse().ok()
+                }
+            })),
+            arp_mode: EnumParam::new(format!("Track {} Arp", index + 1), ArpMode::Off),
+            drum_note: IntParam::new(
+                format!("Track {} Drum Note", index + 1),
+                Self::GM_DRUM_NOTES[index],
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            cc_lanes: std::array::from_fn(|lane| CcLaneParams::new(index, lane)),
+        }
+    }
+}
+
+// work done away from the audio thread
+pub enum Task {
+    // write the pattern in the given slot to a midi file
+    ExportMidi { pattern: usize },
 }
 
-#[derive(Params)]
-struct MyPluginParams {}
+#[derive(Params)]
+struct MyPluginParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<EguiState>,
+
+    // contents of every pattern, copied from the audio thread whenever they change
+    #[persist = "patterns"]
+    patterns: Mutex<[Pattern; NUM_PATTERNS]>,
+
+    // groove extracted from a midi file by the editor, played by the user groove
+    #[persist = "user-groove"]
+    user_groove: Mutex<GrooveTemplate>,
+
+    // controllers mapped to parameters by midi learn
+    #[persist = "cc-map"]
+    cc_map: Mutex<CcMap>,
+
+    // names the editor shows in place of note numbers, only used by the editor
+    #[persist = "note-names"]
+    note_names: Mutex<NoteNames>,
+
+    #[id = "pattern"]
+    pattern: IntParam,
+
+    // a newly selected pattern waits for this boundary, so live changes land on the downbeat
+    #[id = "launch_quantize"]
+    launch_quantize: EnumParam<LaunchQuantize>,
+
+    // track edited by the step, length and rate parameters
+    #[id = "edit_track"]
+    edit_track: IntParam,
+
+    #[id = "steps"]
+    num_steps: IntParam,
+
+    #[id = "rate"]
+    rate: EnumParam<Rate>,
+
+    #[id = "swing"]
+    swing: FloatParam,
+
+    // timing and velocity feel played on top of the pattern
+    #[id = "groove"]
+    groove: EnumParam<Groove>,
+
+    // seed of the selected pattern's random choices, a pattern plays the same
+    // probabilities and humanizing every time from the same seed
+    #[id = "seed"]
+    seed: IntParam,
+
+    // program change sent when the selected pattern starts playing, -1 sends none
+    #[id = "program"]
+    program: IntParam,
+
+    // what the selected pattern launches after playing through follow_repeats times
+    #[id = "follow"]
+    follow: EnumParam<FollowAction>,
+
+    #[id = "follow_repeats"]
+    follow_repeats: IntParam,
+
+    // pattern launched by the pattern follow action
+    #[id = "follow_pattern"]
+    follow_pattern: IntParam,
+
+    // every note sent out is snapped to the scale
+    #[id = "scale"]
+    scale: EnumParam<Scale>,
+
+    #[id = "scale_root"]
+    scale_root: IntParam,
+
+    // degrees of the custom scale, bit n for the note n semitones above the root
+    #[id = "custom_scale"]
+    custom_scale: IntParam,
+
+    // key the patterns are played in, patterns are written in c and moved
+    // to the key along with the scale after they're snapped to it
+    #[id = "key"]
+    key: IntParam,
+
+    // every track plays only its drum note, the steps set when and how hard it's hit
+    #[id = "drum_mode"]
+    drum_mode: BoolParam,
+
+    // write notes played on the midi input into the edited track while the transport runs
+    #[id = "record"]
+    record: BoolParam,
+
+    // fill the steps of the edited track one at a time from the midi input,
+    // whether or not the transport is running
+    #[id = "step_record"]
+    step_record: BoolParam,
+
+    // transpose by the last key held on the midi input, relative to the reference note
+    #[id = "midi_transpose"]
+    midi_transpose: BoolParam,
+
+    #[id = "transpose_ref"]
+    transpose_reference: IntParam,
+
+    // pass the notes played on the midi input through to the output, merged with the sequence
+    #[id = "thru"]
+    thru: BoolParam,
+
+    // keys on one side of the split point control the sequencer and the keys on the
+    // other side are played through, whatever the thru setting, -1 leaves the keyboard whole
+    #[id = "split"]
+    split: IntParam,
+
+    #[id = "split_zone"]
+    split_zone: EnumParam<SplitZone>,
+
+    // lowest of a row of keys launching the patterns in order, -1 launches none
+    #[id = "pattern_keys"]
+    pattern_keys: IntParam,
+
+    // map the next controller moved on the midi input to this parameter
+    #[id = "learn"]
+    learn: EnumParam<LearnTarget>,
+
+    // keep arpeggiating and transposing by the last chord played after its keys are released
+    #[id = "latch"]
+    latch: BoolParam,
+
+    // chance of a step being moved up by up to the octave range, for acid style movement
+    #[id = "octave_jump"]
+    octave_jump: FloatParam,
+
+    #[id = "octave_range"]
+    octave_range: IntParam,
+
+    // notes are sent up to this many milliseconds early or late, for a looser feel
+    #[id = "humanize_time"]
+    humanize_time: FloatParam,
+
+    // velocities are moved up or down by up to this much on every hit
+    #[id = "humanize_vel"]
+    humanize_velocity: FloatParam,
+
+    // time slid steps take to bend to their note
+    #[id = "glide_time"]
+    glide_time: FloatParam,
+
+    // time between the starts of the notes of a chord, 0 plays them together
+    #[id = "strum"]
+    strum: FloatParam,
+
+    #[id = "strum_direction"]
+    strum_direction: EnumParam<StrumDirection>,
+
+    // semitones the receiving instrument bends by at full pitch bend,
+    // steps sliding further than this are played legato instead
+    #[id = "bend_range"]
+    bend_range: IntParam,
+
+    // velocity added to accented steps
+    #[id = "accent_amount"]
+    accent_amount: FloatParam,
+
+    // fraction of the gate accented steps are shortened by
+    #[id = "accent_gate"]
+    accent_gate: FloatParam,
+
+    // what happens to a step when its variation chance comes up
+    #[id = "variation"]
+    variation: EnumParam<Variation>,
+
+    // shapes every velocity sent out, then keeps it between the minimum and maximum
+    #[id = "velocity_curve"]
+    velocity_curve: EnumParam<VelocityCurve>,
+
+    #[id = "velocity_min"]
+    velocity_min: FloatParam,
+
+    #[id = "velocity_max"]
+    velocity_max: FloatParam,
+
+    // play the steps conditioned on a fill, and leave out the ones conditioned on no fill
+    #[id = "fill"]
+    fill: BoolParam,
+
+    // holding this key on the midi input plays a fill while it's held
+    #[id = "fill_note"]
+    fill_note: IntParam,
+
+    // pattern played in place of the current one during a fill
+    #[id = "fill_pattern"]
+    fill_pattern: IntParam,
+
+    // loop the current slice of the pattern, beat repeat style, until it's turned off
+    #[id = "repeat"]
+    repeat: BoolParam,
+
+    // holding this key on the midi input loops the current slice while it's held
+    #[id = "repeat_note"]
+    repeat_note: IntParam,
+
+    // length of the looped slice
+    #[id = "repeat_length"]
+    repeat_length: EnumParam<Rate>,
+
+    // half or double time, a change waits for the next bar
+    #[id = "speed"]
+    speed: EnumParam<Speed>,
+
+    // the two patterns blended by the morph amount, whichever of them is played
+    #[id = "morph_a"]
+    morph_a: IntParam,
+
+    #[id = "morph_b"]
+    morph_b: IntParam,
+
+    #[id = "morph"]
+    morph: FloatParam,
+
+    // play the patterns in the chain instead of the selected pattern
+    #[id = "song"]
+    song_mode: BoolParam,
+
+    // play through the host's pre-roll or count-in instead of staying silent until it ends
+    #[id = "play_preroll"]
+    play_preroll: BoolParam,
+
+    // run from the plugin's own clock at the internal tempo instead of the host's transport
+    #[id = "internal_clock"]
+    internal_clock: BoolParam,
+
+    #[id = "internal_tempo"]
+    internal_tempo: FloatParam,
+
+    // tempo the host's transport is followed at when the host doesn't give one
+    #[id = "fallback_tempo"]
+    fallback_tempo: FloatParam,
+
+    // switch to the internal clock while the host's transport is stopped,
+    // to audition patterns without starting the host
+    #[id = "run_stopped"]
+    run_while_stopped: BoolParam,
+
+    #[nested(array, group = "Chain")]
+    chain: [ChainParams; CHAIN_LEN],
+
+    // midi channel of every track without a channel of its own
+    #[id = "out_channel"]
+    channel: IntParam,
+
+    // steps take turns on the channels from the low to the high rotation channel,
+    // for multitimbral instruments and mono voices on separate channels
+    #[id = "rotate"]
+    rotate_channels: BoolParam,
+
+    #[id = "rotate_low"]
+    rotate_low: IntParam,
+
+    #[id = "rotate_high"]
+    rotate_high: IntParam,
+
+    #[nested(array, group = "Track")]
+    tracks: [TrackParams; NUM_TRACKS],
+
+    #[nested(group = "Pattern Actions")]
+    actions: ActionParams,
+
+    #[nested(group = "LFO")]
+    lfo: LfoParams,
+
+    // notes of the midi input driving the arpeggiator and transposition,
+    // recorded, and passed through by thru
+    #[nested(id_prefix = "keys_in", group = "Keys Input")]
+    keys_input: InputFilterParams,
+
+    #[nested(id_prefix = "record_in", group = "Record Input")]
+    record_input: InputFilterParams,
+
+    #[nested(id_prefix = "thru_in", group = "Thru Input")]
+    thru_input: InputFilterParams,
+
+    #[nested(array, group = "Step")]
+    steps: [StepParams; MAX_STEPS],
+}
+
+impl MyPluginParams {
+    const MAX_SEED: i32 = 9999;
+
+    // degrees of the selected scale, which may be the custom scale
+    fn pitch_classes(&self) -> PitchClasses {
+        self.scale
+            .value()
+            .pitch_classes(self.custom_scale.value() as u16)
+    }
+
+    // zero based midi channel the track's notes are sent on
+    fn track_channel(&self, track: usize) -> u8 {
+        match self.tracks[track].channel.value() {
+            0 => self.channel.value() as u8 - 1,
+            channel => channel as u8 - 1,
+        }
+    }
+}
+
+impl Default for MyPluginParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+            patterns: Mutex::new(std::array::from_fn(|_| Pattern::default())),
+            user_groove: Mutex::new(Groove::OFF),
+            cc_map: Mutex::new(CcMap::default()),
+            note_names: Mutex::new(NoteNames::default()),
+            pattern: IntParam::new(
+                "Pattern",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_PATTERNS as i32,
+                },
+            ),
+            launch_quantize: EnumParam::new("Launch Quantize", LaunchQuantize::Bar),
+            edit_track: IntParam::new(
+                "Edit Track",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_TRACKS as i32,
+                },
+            ),
+            num_steps: IntParam::new(
+                "Steps",
+                Track::DEFAULT_LEN as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_STEPS as i32,
+                },
+            ),
+            rate: EnumParam::new("Rate", Track::DEFAULT_RATE),
+            swing: FloatParam::new(
+                "Swing",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.5,
+                    max: 0.75,
+                },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            groove: EnumParam::new("Groove", Groove::Off),
+            seed: IntParam::new(
+                "Seed",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: Self::MAX_SEED,
+                },
+            ),
+            program: off_or_value(IntParam::new(
+                "Program",
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+            follow: EnumParam::new("Follow Action", FollowAction::Off),
+            follow_repeats: IntParam::new(
+                "Follow After",
+                Pattern::DEFAULT_FOLLOW_REPEATS as i32,
+                IntRange::Linear { min: 1, max: 64 },
+            )
+            .with_unit(" x"),
+            follow_pattern: IntParam::new(
+                "Follow Pattern",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_PATTERNS as i32,
+                },
+            ),
+            scale: EnumParam::new("Scale", Scale::Chromatic),
+            scale_root: IntParam::new("Scale Root", 0, IntRange::Linear { min: 0, max: 11 })
+                .with_value_to_string(scale::v2s_pitch_class())
+                .with_string_to_value(scale::s2v_pitch_class()),
+            custom_scale: IntParam::new(
+                "Custom Scale",
+                PitchClasses::DEFAULT_CUSTOM_MASK as i32,
+                IntRange::Linear {
+                    min: 0,
+                    max: PitchClasses::CHROMATIC as i32,
+                },
+            )
+            .with_value_to_string(scale::v2s_pitch_class_mask())
+            .with_string_to_value(scale::s2v_pitch_class_mask()),
+            key: IntParam::new("Key", 0, IntRange::Linear { min: 0, max: 11 })
+                .with_value_to_string(scale::v2s_pitch_class())
+                .with_string_to_value(scale::s2v_pitch_class()),
+            drum_mode: BoolParam::new("Drum Mode", false),
+            record: BoolParam::new("Record", false),
+            step_record: BoolParam::new("Step Record", false),
+            midi_transpose: BoolParam::new("MIDI Transpose", false),
+            transpose_reference: IntParam::new(
+                "Transpose Reference",
+                60,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            thru: BoolParam::new("MIDI Thru", false),
+            split: off_or_note(IntParam::new(
+                "Split Point",
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+            split_zone: EnumParam::new("Control Zone", SplitZone::Below),
+            pattern_keys: off_or_note(IntParam::new(
+                "Pattern Keys",
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+            learn: EnumParam::new("MIDI Learn", LearnTarget::Off),
+            latch: BoolParam::new("Latch", false),
+            octave_jump: FloatParam::new(
+                "Octave Jump",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            octave_range: IntParam::new("Octave Range", 1, IntRange::Linear { min: 1, max: 2 }),
+            humanize_time: FloatParam::new(
+                "Humanize Time",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 20.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            humanize_velocity: FloatParam::new(
+                "Humanize Velocity",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 0.5 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            glide_time: FloatParam::new(
+                "Glide Time",
+                60.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            strum: FloatParam::new(
+                "Strum",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            strum_direction: EnumParam::new("Strum Direction", StrumDirection::Up),
+            bend_range: IntParam::new("Bend Range", 12, IntRange::Linear { min: 1, max: 48 })
+                .with_unit(" st"),
+            accent_amount: FloatParam::new(
+                "Accent Amount",
+                0.25,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            accent_gate: FloatParam::new(
+                "Accent Gate Cut",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 0.9 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            variation: EnumParam::new("Variation", Variation::Staccato),
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+            velocity_min: FloatParam::new(
+                "Velocity Min",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_max: FloatParam::new(
+                "Velocity Max",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            fill: BoolParam::new("Fill", false),
+            // -1 leaves the fill to the fill parameter
+            fill_note: off_or_note(IntParam::new(
+                "Fill Note",
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+            repeat: BoolParam::new("Repeat", false),
+            // -1 leaves the repeat to the repeat parameter
+            repeat_note: off_or_note(IntParam::new(
+                "Repeat Note",
+                -1,
+                IntRange::Linear { min: -1, max: 127 },
+            )),
+            repeat_length: EnumParam::new("Repeat Length", Rate::Eighth),
+            speed: EnumParam::new("Speed", Speed::Normal),
+            // 0 keeps playing the current pattern
+            fill_pattern: off_or_pattern(IntParam::new(
+                "Fill Pattern",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_PATTERNS as i32,
+                },
+            )),
+            // 0 leaves the pattern unmorphed
+            morph_a: off_or_pattern(IntParam::new(
+                "Morph A",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_PATTERNS as i32,
+                },
+            )),
+            morph_b: off_or_pattern(IntParam::new(
+                "Morph B",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_PATTERNS as i32,
+                },
+            )),
+            morph: FloatParam::new("Morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            song_mode: BoolParam::new("Song Mode", false),
+            play_preroll: BoolParam::new("Play Pre-Roll", false),
+            internal_clock: BoolParam::new("Internal Clock", false),
+            internal_tempo: FloatParam::new(
+                "Internal Tempo",
+                120.0,
+                FloatRange::Linear {
+                    min: 20.0,
+                    max: 300.0,
+                },
+            )
+            .with_unit(" BPM")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            fallback_tempo: FloatParam::new(
+                "Fallback Tempo",
+                120.0,
+                FloatRange::Linear {
+                    min: 20.0,
+                    max: 300.0,
+                },
+            )
+            .with_unit(" BPM")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            run_while_stopped: BoolParam::new("Run While Stopped", false),
+            chain: std::array::from_fn(ChainParams::new),
+            channel: IntParam::new("Channel", 1, IntRange::Linear { min: 1, max: 16 }),
+            rotate_channels: BoolParam::new("Channel Rotation", false),
+            rotate_low: IntParam::new(
+                "Rotation Low Channel",
+                1,
+                IntRange::Linear { min: 1, max: 16 },
+            ),
+            rotate_high: IntParam::new(
+                "Rotation High Channel",
+                4,
+                IntRange::Linear { min: 1, max: 16 },
+            ),
+            tracks: std::array::from_fn(TrackParams::new),
+            actions: ActionParams::default(),
+            lfo: LfoParams::default(),
+            keys_input: InputFilterParams::new("Keys"),
+            record_input: InputFilterParams::new("Record"),
+            thru_input: InputFilterParams::new("Thru"),
+            steps: std::array::from_fn(StepParams::new),
+        }
+    }
+}
+
+// pitch bend left on a channel by a slide
+#[derive(Clone, Copy)]
+struct Bend {
+    channel: u8,
+    semitones: i32,
+}
+
+// pattern launched when the song reaches the given bar, none stops playing
+#[derive(Clone, Copy)]
+struct Launch {
+    pattern: Option<usize>,
+    bar: i64,
+}
+
+// slice of the song being looped by a repeat
+#[derive(Clone, Copy)]
+struct Repeat {
+    start_beats: f64,
+    beats: f64,
+}
+
+#[derive(Clone, Copy)]
+struct HeldNotes {
+    channel: u8,
+    notes: [Option<u8>; MAX_CHORD_NOTES],
+}
+
+// playback state kept separately for every track
+struct TrackState {
+    // notes left sounding at the end of a step because the next step is tied
+    held_notes: Option<HeldNotes>,
+
+    // the held notes are bent away from their pitch by a slide
+    bend: Option<Bend>,
+
+    // step that was scheduled ahead of its boundary because of a negative offset
+    early_step: Option<i64>,
+
+    // position in the arpeggio of the held keys
+    arp_position: usize,
+
+    // pattern the last step was played from
+    pattern: Option<usize>,
+
+    // step count the pattern started playing at, trig conditions count its loops from here
+    pattern_start: i64,
+}
+
+impl Default for TrackState {
+    fn default() -> Self {
+        Self {
+            held_notes: None,
+            bend: None,
+            early_step: None,
+            arp_position: 0,
+            pattern: None,
+            pattern_start: 0,
+        }
+    }
+}
+
+pub struct MyPlugin {
+    params: Arc<MyPluginParams>,
+    patterns: [Pattern; NUM_PATTERNS],
+
+    // pattern being played, a pattern taking over for a new bar starts at the next step
+    active_pattern: usize,
+
+    // pattern that's been launched and the bar it started at, none after a
+    // stop follow action, and the launch waiting for its bar
+    launched_pattern: Option<usize>,
+    launched_bar: i64,
+    launch: Option<Launch>,
+
+    // value of the pattern parameter as of the last buffer, a change launches the pattern
+    last_selected_pattern: usize,
+
+    // pattern picked with the pattern keys, launched like a newly selected pattern
+    key_pattern: Option<usize>,
+
+    // step parameter values as of the last buffer, used to find the ones that changed
+    last_step_params: [Step; MAX_STEPS],
+    last_num_steps: usize,
+    last_rate: Rate,
+    last_seed: u32,
+    last_program: Option<u8>,
+    last_follow: FollowAction,
+    last_follow_repeats: u32,
+    last_follow_pattern: usize,
+
+    pattern_actions: PatternActions,
+
+    // the patterns were edited since they were last copied to the saved state
+    patterns_changed: bool,
+
+    // pattern handed over by the editor to replace the selected pattern
+    incoming_pattern: Arc<Mutex<Option<Pattern>>>,
+
+    notes: NoteScheduler,
+    sounding: SoundingNotes,
+
+    // step of the edited track being played, shown by the editor
+    playhead: Arc<AtomicI32>,
+
+    // why the sequencer is or isn't playing, shown in the editor
+    diagnostics: Arc<Diagnostics>,
+
+    // outcome of the last midi export, left by the background thread for the editor
+    export_status: Arc<Mutex<Option<String>>>,
+
+    // pattern whose program change was last sent, none sends the next pattern's again
+    program_pattern: Option<usize>,
+
+    // random choices of the step being played, seeded from its pattern's seed
+    rng: Rng,
+
+    // random numbers for actions, which should differ every time
+    action_rng: Rng,
+    held_keys: HeldKeys,
+
+    // number of steps played on rotating channels since playback started
+    rotation: usize,
+
+    // notes passed through from the midi input that haven't ended, their
+    // note offs are passed through even once thru is turned off
+    thru_notes: SoundingNotes,
+
+    // the fill note is held on the midi input
+    fill_held: bool,
+
+    // the repeat note is held on the midi input
+    repeat_held: bool,
+    repeat: Option<Repeat>,
+
+    // speed the tracks are playing at, lags the speed parameter until the next bar
+    speed: Speed,
+
+    // speed taken up at the bar line falling at this sample index of the current buffer
+    speed_change: Option<(Speed, u32)>,
+
+    recorder: Recorder,
+    tracks: [TrackState; NUM_TRACKS],
+
+    buffer_sample_rate: Option<f32>,
+    engine: Engine,
+    free_clock: FreeClock,
+    midi_clock: MidiClock,
+    lfo: Lfo,
+
+    // copy of the user groove for the audio thread
+    user_groove: GrooveTemplate,
+    midi_learn: MidiLearn,
+}
+
+impl MyPlugin {
+    // aftertouch is sent this long after its note starts, so instruments
+    // see the note first
+    const PRESSURE_DELAY_SAMPLES: u64 = 16;
+
+    // number of pitch bend messages a slide is made of
+    const SLIDE_BEND_STEPS: u32 = 32;
+
+    // lowest velocity humanizing can bring a hit down to
+    const MIN_HUMANIZED_VELOCITY: f32 = 1.0 / 127.0;
+
+    fn init(&mut self) {
+        self.engine = Engine::default();
+        self.free_clock.reset();
+        self.lfo.reset();
+        self.release_all();
+        self.playhead.store(editor::NO_PLAYHEAD, Ordering::Relaxed);
+
+        // the internal clock starts over, so hardware following it is started again
+        self.schedule_midi_clock(None);
+        self.tracks = Default::default();
+        self.held_keys.clear();
+        self.fill_held = false;
+        self.repeat_held = false;
+        self.repeat = None;
+        self.launch = None;
+        self.recorder.clear();
+    }
+
+    // pattern selected by the pattern parameter
+    fn selected_pattern(&self) -> usize {
+        self.params.pattern.value() as usize - 1
+    }
+
+    // track edited by the step parameters
+    fn edit_track(&self) -> usize {
+        self.params.edit_track.value() as usize - 1
+    }
+
+    // pattern to play at the given bar, which follows the chain in song mode
+    fn bar_pattern(&self, bar: i64) -> Option<usize> {
+        if self.fill_active() {
+            if let Some(pattern) = (self.params.fill_pattern.value() as usize).checked_sub(1) {
+                return Some(pattern);
+            }
+        }
+
+        if self.params.song_mode.value() {
+            let chain: [ChainEntry; CHAIN_LEN] =
+                std::array::from_fn(|index| self.params.chain[index].entry());
+            if let Some(pattern) = chain::pattern_at_bar(&chain, bar) {
+                return Some(pattern);
+            }
+        }
+
+        match self.launch {
+            Some(launch) if bar >= launch.bar => launch.pattern,
+            _ => self.launched_pattern,
+        }
+    }
+
+    // launch a newly selected pattern at the launch quantize boundary, or the
+    // launched pattern's follow action once it's played through.
+    // a pattern selected while stopped starts straight away
+    fn update_launch(&mut self, clock: &Clock) {
+        if let Some(launch) = self.launch.filter(|launch| clock.bar_number >= launch.bar) {
+            self.launched_pattern = launch.pattern;
+            self.launched_bar = launch.bar;
+            self.launch = None;
+        }
+
+        // a controller mapped to the pattern launches patterns without selecting them
+        let selected_pattern = self.learned_value(LearnTarget::Pattern, &self.params.pattern);
+        let selected_pattern = selected_pattern as usize - 1;
+        let newly_selected = selected_pattern != self.last_selected_pattern;
+        self.last_selected_pattern = selected_pattern;
+
+        // the pattern keys launch patterns the same way, taking over from the parameter
+        let key_pattern = self.key_pattern.take();
+        let newly_selected = newly_selected || key_pattern.is_some();
+        let selected_pattern = key_pattern.unwrap_or(selected_pattern);
+
+        if !clock.was_playing || clock.relocated {
+            self.launched_pattern = Some(selected_pattern);
+            self.launched_bar = clock.bar_number;
+            self.launch = None;
+        } else if newly_selected {
+            let quantize = self.params.launch_quantize.value();
+            match quantize.launch_bar(clock.bar_number) {
+                Some(bar) => {
+                    nih_log!("launch pattern {} at bar {bar}", selected_pattern + 1);
+                    self.launch = Some(Launch {
+                        pattern: Some(selected_pattern),
+                        bar,
+                    });
+                }
+                None => {
+                    self.launched_pattern = Some(selected_pattern);
+                    self.launched_bar = clock.bar_number;
+                    self.launch = None;
+                }
+            }
+        } else if self.launch.is_none() {
+            self.schedule_follow(clock);
+        }
+    }
+
+    // launch whatever follows the launched pattern when its last repeat ends,
+    // decided during its last bar so the follow settings can change until then
+    fn schedule_follow(&mut self, clock: &Clock) {
+        let Some(pattern) = self.launched_pattern else {
+            return;
+        };
+        let follow = self.patterns[pattern].follow;
+        if follow == FollowAction::Off {
+            return;
+        }
+
+        let bars = self.patterns[pattern].bars(clock.bar_beats / self.speed.step_factor())
+            * self.patterns[pattern].follow_repeats.max(1) as i64;
+        let bar = (self.launched_bar + bars).max(clock.bar_number + 1);
+        if bar > clock.bar_number + 1 {
+            return;
+        }
+
+        let next_pattern = match follow {
+            FollowAction::Off => return,
+            FollowAction::Next => Some((pattern + 1) % NUM_PATTERNS),
+            FollowAction::Previous => Some((pattern + NUM_PATTERNS - 1) % NUM_PATTERNS),
+            FollowAction::Random => Some(self.random_pattern(pattern)),
+            FollowAction::Pattern => Some(self.patterns[pattern].follow_pattern),
+            FollowAction::Stop => None,
+        };
+        match next_pattern {
+            Some(next_pattern) => nih_log!("follow with pattern {} at bar {bar}", next_pattern + 1),
+            None => nih_log!("follow with stop at bar {bar}"),
+        }
+        self.launch = Some(Launch {
+            pattern: next_pattern,
+            bar,
+        });
+    }
+
+    // any pattern other than the given one that isn't empty, or the
+    // given pattern if every other one is empty
+    fn random_pattern(&mut self, pattern: usize) -> usize {
+        let mut patterns =
+            (0..NUM_PATTERNS).filter(|&index| index != pattern && !self.patterns[index].is_empty());
+        let count = patterns.clone().count();
+        if count == 0 {
+            return pattern;
+        }
+        let index = (self.action_rng.next_u64() % count as u64) as usize;
+        patterns.nth(index).unwrap_or(pattern)
+    }
+
+    // the given step of a track, blended with the other morph pattern when
+    // the pattern is one of the two being morphed
+    fn morphed_step(&self, pattern: usize, track: usize, step_count: i64) -> Step {
+        let step = *self.patterns[pattern].tracks[track].step(step_count);
+        let morph_a = (self.params.morph_a.value() as usize).checked_sub(1);
+        let morph_b = (self.params.morph_b.value() as usize).checked_sub(1);
+        match (morph_a, morph_b) {
+            (Some(a), Some(b)) if a != b && (pattern == a || pattern == b) => morph::morph(
+                self.patterns[a].tracks[track].step(step_count),
+                self.patterns[b].tracks[track].step(step_count),
+                self.params.morph.value(),
+            ),
+            _ => step,
+        }
+    }
+
+    // modulation of the step parameters plays on top of the edited track's steps,
+    // the pattern keeps the values the parameters are set to
+    fn modulate_step(&self, pattern: usize, track: usize, step_index: usize, step: &mut Step) {
+        if pattern != self.selected_pattern() || track != self.edit_track() {
+            return;
+        }
+
+        let step_params = &self.params.steps[step_index];
+        step.apply_changes(&step_params.step(false), &step_params.step(true));
+    }
+
+    // a fill is played while the fill parameter is on or the fill note is held
+    fn fill_active(&self) -> bool {
+        self.params.fill.value() || self.fill_held
+    }
+
+    // write changed step parameters into the selected pattern,
+    // so the step parameters edit whichever pattern and track is selected
+    fn update_patterns(&mut self) {
+        let selected_pattern = self.selected_pattern();
+        let edit_track = self.edit_track();
+
+        let seed = self.params.seed.value() as u32;
+        if seed != self.last_seed {
+            self.patterns[selected_pattern].seed = seed;
+            self.last_seed = seed;
+            self.patterns_changed = true;
+        }
+
+        let program = u8::try_from(self.params.program.value()).ok();
+        if program != self.last_program {
+            self.patterns[selected_pattern].program = program;
+            self.last_program = program;
+            self.patterns_changed = true;
+        }
+
+        let follow = self.params.follow.value();
+        if follow != self.last_follow {
+            self.patterns[selected_pattern].follow = follow;
+            self.last_follow = follow;
+            self.patterns_changed = true;
+        }
+
+        let follow_repeats = self.params.follow_repeats.value() as u32;
+        if follow_repeats != self.last_follow_repeats {
+            self.patterns[selected_pattern].follow_repeats = follow_repeats;
+            self.last_follow_repeats = follow_repeats;
+            self.patterns_changed = true;
+        }
+
+        let follow_pattern = self.params.follow_pattern.value() as usize - 1;
+        if follow_pattern != self.last_follow_pattern {
+            self.patterns[selected_pattern].follow_pattern = follow_pattern;
+            self.last_follow_pattern = follow_pattern;
+            self.patterns_changed = true;
+        }
+
+        let track = &mut self.patterns[selected_pattern].tracks[edit_track];
+
+        let num_steps = self.params.num_steps.value() as usize;
+        if num_steps != self.last_num_steps {
+            track.len = num_steps;
+            self.last_num_steps = num_steps;
+            self.patterns_changed = true;
+        }
+
+        let rate = self.params.rate.value();
+        if rate != self.last_rate {
+            track.rate = rate;
+            self.last_rate = rate;
+            self.patterns_changed = true;
+        }
+
+        for ((step, last_step), step_params) in track
+            .steps
+            .iter_mut()
+            .zip(&mut self.last_step_params)
+            .zip(&self.params.steps)
+        {
+            let new_step = step_params.step(false);
+            if new_step != *last_step {
+                step.apply_changes(last_step, &new_step);
+                *last_step = new_step;
+                self.patterns_changed = true;
+            }
+        }
+    }
+
+    // take on the current step parameter values without writing them into a pattern,
+    // so restored parameters don't overwrite the restored patterns
+    fn sync_step_params(&mut self) {
+        self.last_num_steps = self.params.num_steps.value() as usize;
+        self.last_rate = self.params.rate.value();
+        self.last_seed = self.params.seed.value() as u32;
+        self.last_program = u8::try_from(self.params.program.value()).ok();
+        self.last_follow = self.params.follow.value();
+        self.last_follow_repeats = self.params.follow_repeats.value() as u32;
+        self.last_follow_pattern = self.params.follow_pattern.value() as usize - 1;
+        for (last_step, step_params) in self.last_step_params.iter_mut().zip(&self.params.steps) {
+            *last_step = step_params.step(false);
+        }
+    }
+
+    // fill the edited track of the selected pattern with random notes of the scale
+    fn randomize_edit_track(&mut self) {
+        let selected_pattern = self.selected_pattern();
+        let edit_track = self.edit_track();
+        nih_log!(
+            "randomize pattern {} track {}",
+            selected_pattern + 1,
+            edit_track + 1
+        );
+
+        let track = generate::randomize(
+            &self.patterns[selected_pattern].tracks[edit_track],
+            &mut self.action_rng,
+            self.params.pitch_classes(),
+            self.params.scale_root.value() as u8,
+            self.params.actions.random_octaves.value() as u8,
+            self.params.actions.random_density.value(),
+        );
+        self.pattern_actions
+            .replace_track(&mut self.patterns, selected_pattern, edit_track, track);
+        self.patterns_changed = true;
+    }
+
+    // make small random edits to the edited track of the selected pattern
+    fn mutate_edit_track(&mut self) {
+        let selected_pattern = self.selected_pattern();
+        let edit_track = self.edit_track();
+        nih_log!(
+            "mutate pattern {} track {}",
+            selected_pattern + 1,
+            edit_track + 1
+        );
+
+        let track = generate::mutate(
+            &self.patterns[selected_pattern].tracks[edit_track],
+            &mut self.action_rng,
+            self.params.pitch_classes(),
+            self.params.scale_root.value() as u8,
+            self.params.actions.mutate_amount.value(),
+        );
+        self.pattern_actions
+            .replace_track(&mut self.patterns, selected_pattern, edit_track, track);
+        self.patterns_changed = true;
+    }
+
+    // replace the selected pattern with one loaded by the editor, if there is one
+    fn load_incoming_pattern(&mut self) {
+        let Ok(mut incoming_pattern) = self.incoming_pattern.try_lock() else {
+            return;
+        };
+
+        if let Some(pattern) = incoming_pattern.take() {
+            let selected_pattern = self.selected_pattern();
+            self.pattern_actions
+                .load(&mut self.patterns, selected_pattern, pattern);
+            self.patterns_changed = true;
+        }
+    }
+
+    // copy the patterns to the saved state, skipped while the state is being
+    // read or written and tried again on the next buffer
+    fn save_patterns(&mut self) {
+        if !self.patterns_changed {
+            return;
+        }
+
+        if let Ok(mut saved_patterns) = self.params.patterns.try_lock() {
+            saved_patterns.clone_from(&self.patterns);
+            self.patterns_changed = false;
+        }
+    }
+
+    // position of the host's transport at the start of this buffer, if it can be followed
+    fn transport_position(&self, transport: &Transport) -> Option<TransportSnapshot> {
+        if transport.preroll_active.unwrap_or(false) && !self.params.play_preroll.value() {
+            nih_log!("preroll active: do nothing");
+            self.diagnostics.report(Diagnostic::Preroll);
+            return None;
+        }
+
+        // without a tempo from the host the position in beats
+        // is worked out from the time at the fallback tempo
+        let tempo: f64 = match transport.tempo {
+            Some(value) => value,
+            None => {
+                nih_log!("missing tempo");
+                self.params.fallback_tempo.value() as f64
+            }
+        };
+
+        let pos_beats = match transport.pos_beats().or_else(|| {
+            transport
+                .pos_seconds()
+                .map(|seconds| seconds * tempo / 60.0)
+        }) {
+            Some(value) => value,
+            None => {
+                nih_log!("missing pos_beats");
+                self.diagnostics.report(Diagnostic::MissingPosition);
+                return None;
+            }
+        };
+
+        // duration of a bar in beats, assuming 4/4 when the host doesn't say
+        let bar_beats = match (transport.time_sig_numerator, transport.time_sig_denominator) {
+            (Some(numerator), Some(denominator)) => numerator as f64 * 4.0 / denominator as f64,
+            _ => 4.0,
+        };
+
+        Some(TransportSnapshot {
+            pos_beats,
+            pos_samples: transport.pos_samples(),
+            tempo,
+            bar_beats,
+            bar_number: transport.bar_number().map(|value| value as i64),
+            bar_start_beats: transport.bar_start_pos_beats(),
+        })
+    }
+
+    // position of the internal clock at the start of this buffer, which runs in 4/4
+    fn free_position(&mut self, buffer_samples: usize, sample_rate: f64) -> TransportSnapshot {
+        let tempo = self.params.internal_tempo.value() as f64;
+        let (pos_beats, pos_samples) = self
+            .free_clock
+            .advance(buffer_samples, sample_rate * 60.0 / tempo);
+
+        TransportSnapshot {
+            pos_beats,
+            pos_samples: Some(pos_samples),
+            tempo,
+            bar_beats: 4.0,
+            bar_number: None,
+            bar_start_beats: None,
+        }
+    }
+
+    // the tracks follow the internal clock instead of the host's transport
+    fn uses_free_clock(&self, transport: &Transport) -> bool {
+        self.params.internal_clock.value()
+            || (!transport.playing && self.params.run_while_stopped.value())
+    }
+
+    // transport state for this buffer, if the tracks can be played
+    fn clock(&mut self, transport: &Transport, buffer_samples: usize) -> Option<Clock> {
+        let buffer_sample_rate = match self.buffer_sample_rate {
+            Some(value) => value,
+            None => {
+                nih_log!("missing buffer_sample_rate");
+                self.diagnostics.report(Diagnostic::MissingSampleRate);
+                return None;
+            }
+        };
+
+        let free_running = self.uses_free_clock(transport);
+        let snapshot = if free_running {
+            self.free_position(buffer_samples, buffer_sample_rate as f64)
+        } else {
+            // the internal clock starts from the beginning whenever it's switched on
+            self.free_clock.reset();
+            self.transport_position(transport)?
+        };
+
+        let swing_delay =
+            (self.learned_value(LearnTarget::Swing, &self.params.swing) as f64 - 0.5) * 2.0;
+        let clock = self.engine.clock(
+            &snapshot,
+            buffer_samples,
+            buffer_sample_rate as f64,
+            swing_delay,
+            free_running,
+        );
+
+        if clock.relocated {
+            // notes from the old position would be stray at the new one
+            self.diagnostics.report(Diagnostic::Relocated {
+                pos_beats: clock.pos_beats as f32,
+            });
+            self.release_all();
+            self.tracks = Default::default();
+            self.recorder.clear();
+        }
+
+        if transport.tempo.is_none() && !free_running {
+            self.diagnostics.report(Diagnostic::FallbackTempo {
+                tempo: snapshot.tempo as f32,
+            });
+        } else {
+            self.diagnostics.report(Diagnostic::Playing);
+        }
+
+        // notes already waiting keep their distance in beats when the tempo changes
+        self.notes.retime(clock.samples_per_beat);
+
+        Some(clock)
+    }
+
+    // play the steps of every track that start in this buffer
+    fn play_buffer(&mut self, clock: &Clock) {
+        // every track keeps its own step phase, so tracks at different
+        // rates start their steps at different points in the buffer
+        let speed_change = self.speed_change.take();
+        let until = speed_change.map(|(_, timing)| timing);
+        for track in 0..NUM_TRACKS {
+            let step_beats = self.step_beats(self.active_pattern, track);
+            let step_start = self.engine.find_step_start(step_beats, clock);
+            self.play_steps(track, clock, step_start, until);
+        }
+
+        // the rest of the buffer from the bar line on is played at the new speed
+        if let Some((speed, timing)) = speed_change {
+            nih_log!("speed {speed:?}");
+            self.speed = speed;
+            for track in 0..NUM_TRACKS {
+                let step_beats = self.step_beats(self.active_pattern, track);
+                let step_start = self.engine.find_step_start_from(step_beats, clock, timing);
+                self.play_steps(track, clock, step_start, None);
+            }
+        }
+    }
+
+    // step grid of the given track in the given pattern
+    fn grid(&self, pattern: usize, track: usize, clock: &Clock) -> Grid {
+        clock.grid(self.step_beats(pattern, track))
+    }
+
+    // duration of a straight step of the given track in beats at the current speed
+    fn step_beats(&self, pattern: usize, track: usize) -> f64 {
+        self.patterns[pattern].tracks[track].rate.beats() * self.speed.step_factor()
+    }
+
+    // schedule the notes for a step,
+    // start is the sample index of the step boundary relative to this buffer
+    fn play_step(
+        &mut self,
+        track: usize,
+        pattern: usize,
+        step_count: i64,
+        start: f64,
+        step_samples: f64,
+        straight_step_samples: f64,
+    ) {
+        let step_index = self.patterns[pattern].tracks[track].step_index(step_count);
+        let mut step = self.morphed_step(pattern, track, step_count);
+        self.modulate_step(pattern, track, step_index, &mut step);
+
+        // the same step makes the same random choices every time it comes around
+        // from the same point in the song
+        self.rng = Rng::for_step(self.patterns[pattern].seed, track, step_count);
+        self.notes.reseed(self.rng.next_u64());
+
+        let channel = self.params.track_channel(track);
+        let muted = self.params.tracks[track].mute.value();
+
+        // cc lanes are sent on the step boundary, whether or not the step plays
+        if !muted {
+            let sample = self.notes.sample_at(start);
+            for (lane, value) in step.cc.into_iter().enumerate() {
+                if let Some(value) = value {
+                    let cc = self.params.tracks[track].cc_lanes[lane].number.value() as u8;
+                    self.schedule_note(ScheduledNote {
+                        kind: NoteKind::Cc {
+                            cc,
+                            value: value as f32 / 127.0,
+                        },
+                        channel,
+                        note: 0,
+                        sample,
+                    });
+                }
+            }
+        }
+
+        // the groove only ever plays steps late, so it never needs an early step
+        let groove = self.params.groove.value().template(&self.user_groove);
+        step.offset += groove.timing(step_count);
+        step.velocity = (step.velocity + groove.velocity(step_count)).clamp(0.0, 1.0);
+
+        // nudged off the grid, but never earlier than this buffer
+        let start = (start + step.offset as f64 * straight_step_samples).max(0.0);
+
+        // end the notes left sounding by the previous pattern, which breaks any tie into this step
+        let last_pattern = self.tracks[track].pattern.replace(pattern);
+        if last_pattern != Some(pattern) {
+            self.tracks[track].pattern_start = step_count;
+        }
+        if last_pattern.is_some_and(|last_pattern| last_pattern != pattern) {
+            if let Some(held) = self.tracks[track].held_notes.take() {
+                self.release_channel(held.channel, start);
+            }
+            self.release_channel(channel, start);
+        }
+
+        // notes held over from the previous step because this step is tied to it
+        let held_notes = self.tracks[track].held_notes.take();
+
+        let iteration = self.patterns[pattern].tracks[track]
+            .iteration_since(step_count, self.tracks[track].pattern_start);
+        let mut plays = step.enabled
+            && !muted
+            && step.condition.passes(iteration, self.fill_active())
+            && self.rng.next_f32() < step.probability;
+
+        // drum tracks aren't arpeggiated
+        let drum_note = self.drum_note(track);
+        let arp_mode = match drum_note {
+            Some(_) => ArpMode::Off,
+            None => self.params.tracks[track].arp_mode.value(),
+        };
+        if plays && arp_mode != ArpMode::Off {
+            // the next held key takes the place of the step's notes
+            let position = match arp_mode {
+                ArpMode::Random => self.rng.next_u64() as usize,
+                _ => {
+                    let position = self.tracks[track].arp_position;
+                    self.tracks[track].arp_position = position.wrapping_add(1);
+                    position
+                }
+            };
+
+            match self
+                .held_keys
+                .arp_note(arp_mode, position, self.params.latch.value())
+            {
+                Some(note) => {
+                    step.note = note;
+                    step.chord_notes = Step::DEFAULT_CHORD_NOTES;
+                    step.pool = Step::DEFAULT_POOL;
+                }
+                None => {
+                    // nothing to play until a key is held
+                    plays = false;
+                }
+            }
+        }
+
+        // rotated steps don't know their channel yet, they take the channel of their tie
+        let rotating = self.params.rotate_channels.value();
+        let mut tied_notes = match held_notes {
+            Some(held)
+                if plays && (step.tie || step.slide) && (rotating || held.channel == channel) =>
+            {
+                held.notes
+            }
+            Some(held) => {
+                // the tie no longer applies, end the held notes where this step starts
+                let sample = self.notes.sample_at(start);
+                for note in held.notes.into_iter().flatten() {
+                    self.schedule_note(ScheduledNote {
+                        kind: NoteKind::Off,
+                        channel: held.channel,
+                        note,
+                        sample,
+                    });
+                }
+                [None; MAX_CHORD_NOTES]
+            }
+            None => [None; MAX_CHORD_NOTES],
+        };
+
+        if !plays {
+            // rest, or step skipped this time around
+            return;
+        }
+
+        let channel = match held_notes {
+            Some(held) if tied_notes.iter().any(Option::is_some) => held.channel,
+            _ if rotating => self.rotation_channel(),
+            _ => channel,
+        };
+
+        // accented steps hit harder, and shorter for a tb-303 style snap
+        if step.accent {
+            step.velocity = (step.velocity + self.params.accent_amount.value()).min(1.0);
+            step.gate = (step.gate * (1.0 - self.params.accent_gate.value())).max(Step::MIN_GATE);
+        }
+
+        // steps without a variation chance leave the random choices as they were
+        if step.variation > 0.0 && self.rng.next_f32() < step.variation {
+            self.params.variation.value().apply(&mut step);
+        }
+
+        // only steps with a pool take another random choice for their note
+        if step.pool.iter().any(Option::is_some) {
+            step.note = step.pick_note(self.rng.next_f32());
+        }
+
+        let notes = match drum_note {
+            Some(note) => std::array::from_fn(|voice| (voice == 0).then_some(note)),
+            None => self.octave_jump(self.output_notes(track, &step)),
+        };
+
+        // the last notes of this step are held into the next step when it's tied
+        let next_step = self.patterns[pattern].tracks[track].step(step_count + 1);
+        let hold_last_notes = next_step.enabled && (next_step.tie || next_step.slide);
+
+        // ratchets split the step into evenly spaced retriggers
+        let ratchet_samples = step_samples / step.ratchets as f64;
+        let gate_samples = (ratchet_samples * step.gate as f64).round().max(1.0) as u64;
+
+        // a slid note is held for the gate of the step it slid to, ratchets are left out
+        let slide_end = (!hold_last_notes).then(|| {
+            self.notes.sample_at(start) + (step_samples * step.gate as f64).round().max(1.0) as u64
+        });
+        if step.slide && self.slide(track, channel, tied_notes, notes[0], start, slide_end) {
+            return;
+        }
+
+        // notes bent by a slide are ended and the bend taken off before new notes start
+        if let Some(bend) = self.tracks[track].bend.take() {
+            let sample = self.notes.sample_at(start);
+            for note in tied_notes.into_iter().flatten() {
+                self.schedule_note(ScheduledNote {
+                    kind: NoteKind::Off,
+                    channel,
+                    note,
+                    sample,
+                });
+            }
+            tied_notes = [None; MAX_CHORD_NOTES];
+            self.schedule_bend(bend.channel, 0.0, sample);
+        }
+
+        let sample_rate = self.buffer_sample_rate.unwrap_or(44100.0) as f64;
+        let strum_samples = self.params.strum.value() as f64 / 1000.0 * sample_rate;
+
+        for ratchet in 0..step.ratchets {
+            // ratchets and note offs past the end of this buffer wait in the scheduler
+            let sample = self
+                .notes
+                .sample_at(start + ratchet as f64 * ratchet_samples);
+
+            // every ratchet is strummed again, alternating strums take turns from step to step
+            // and from ratchet to ratchet
+            let strum_delays = self.params.strum_direction.value().delays(
+                &notes,
+                strum_samples,
+                step_count + ratchet as i64,
+            );
+
+            let velocity = self.humanize_velocity(self.hit_velocity(&step));
+
+            // the first ratchet continues the notes held over from the previous step
+            let tied_notes = if ratchet == 0 {
+                tied_notes
+            } else {
+                [None; MAX_CHORD_NOTES]
+            };
+
+            // keep holding tied notes of the same pitch instead of retriggering them
+            for (voice, note) in notes.into_iter().flatten().enumerate() {
+                if !tied_notes.contains(&Some(note)) {
+                    let sample = sample + strum_delays[voice];
+                    self.schedule_note(ScheduledNote {
+                        kind: NoteKind::On {
+                            velocity,
+                            voice_id: Self::voice_id(track, step_index, voice),
+                        },
+                        channel,
+                        note,
+                        sample,
+                    });
+
+                    if let Some(pressure) = step.pressure {
+                        self.schedule_note(ScheduledNote {
+                            kind: NoteKind::PolyPressure {
+                                pressure: pressure as f32 / 127.0,
+                            },
+                            channel,
+                            note,
+                            sample: sample + Self::PRESSURE_DELAY_SAMPLES,
+                        });
+                    }
+
+                    self.schedule_expressions(&step, channel, note, sample + 1);
+                }
+            }
+
+            // legato, held notes left out of this step end just after the new notes start
+            for note in tied_notes.into_iter().flatten() {
+                if !notes.contains(&Some(note)) {
+                    self.schedule_note(ScheduledNote {
+                        kind: NoteKind::Off,
+                        channel,
+                        note,
+                        sample: sample + 1,
+                    });
+                }
+            }
+
+            if hold_last_notes && ratchet == step.ratchets - 1 {
+                self.tracks[track].held_notes = Some(HeldNotes { channel, notes });
+            } else {
+                // strummed notes all end together, unless the strum outlasts the gate
+                for (voice, note) in notes.into_iter().flatten().enumerate() {
+                    self.schedule_note(ScheduledNote {
+                        kind: NoteKind::Off,
+                        channel,
+                        note,
+                        sample: (sample + gate_samples).max(sample + strum_delays[voice] + 1),
+                    });
+                }
+            }
+        }
+    }
+
+    // next channel of the rotation, zero based
+    fn rotation_channel(&mut self) -> u8 {
+        let low = self.params.rotate_low.value() as usize;
+        let high = (self.params.rotate_high.value() as usize).max(low);
+        let channel = low - 1 + self.rotation % (high - low + 1);
+        self.rotation = self.rotation.wrapping_add(1);
+        channel as u8
+    }
+
+    // the same voice of the same step always plays with the same voice id,
+    // so hosts can pair note ons and offs and modulate a single voice
+    fn voice_id(track: usize, step_index: usize, voice: usize) -> i32 {
+        ((track * MAX_STEPS + step_index) * MAX_CHORD_NOTES + voice) as i32
+    }
+
+    // notes passed through from the midi input have voice ids of their own,
+    // after those of every voice of every step
+    fn thru_voice_id(channel: u8, note: u8) -> i32 {
+        (NUM_TRACKS * MAX_STEPS * MAX_CHORD_NOTES + channel as usize * 128 + note as usize) as i32
+    }
+
+    // schedule the step's note expressions for one of its notes,
+    // expressions left at their defaults aren't sent
+    fn schedule_expressions(&mut self, step: &Step, channel: u8, note: u8, sample: u64) {
+        let expressions = [
+            (step.volume != Step::DEFAULT_VOLUME).then(|| NoteKind::Volume {
+                gain: util::db_to_gain(step.volume),
+            }),
+            (step.pan != Step::DEFAULT_PAN).then_some(NoteKind::Pan { pan: step.pan }),
+            (step.tuning != Step::DEFAULT_TUNING).then_some(NoteKind::Tuning {
+                tuning: step.tuning,
+            }),
+        ];
+        for kind in expressions.into_iter().flatten() {
+            self.schedule_note(ScheduledNote {
+                kind,
+                channel,
+                note,
+                sample,
+            });
+        }
+    }
+
+    // bend the first note held over from the previous step to the step's note instead
+    // of playing it, ending it at the given sample or else holding it into the next step.
+    // returns false if there's no note to slide from or the step's note is out of
+    // bend range, the step is played legato then.
+    fn slide(
+        &mut self,
+        track: usize,
+        channel: u8,
+        held_notes: [Option<u8>; MAX_CHORD_NOTES],
+        note: Option<u8>,
+        start: f64,
+        end: Option<u64>,
+    ) -> bool {
+        let (Some(held_note), Some(note)) = (held_notes[0], note) else {
+            return false;
+        };
 
-impl Default for MyPluginParams {
-    fn default() -> Self {
-        Self {}
+        let from = self.tracks[track].bend.map_or(0, |bend| bend.semitones);
+        let to = note as i32 - held_note as i32;
+        if to.abs() > self.params.bend_range.value() {
+            return false;
+        }
+
+        // only a single note slides, the rest of a held chord ends
+        let sample = self.notes.sample_at(start);
+        for note in held_notes.into_iter().skip(1).flatten() {
+            self.schedule_note(ScheduledNote {
+                kind: NoteKind::Off,
+                channel,
+                note,
+                sample,
+            });
+        }
+
+        let sample_rate = self.buffer_sample_rate.unwrap_or(44100.0) as f64;
+        let glide_samples = self.params.glide_time.value() as f64 / 1000.0 * sample_rate;
+        for bend_step in 1..=Self::SLIDE_BEND_STEPS {
+            let fraction = bend_step as f64 / Self::SLIDE_BEND_STEPS as f64;
+            let semitones = from as f64 + (to - from) as f64 * fraction;
+            let sample = self.notes.sample_at(start + glide_samples * fraction);
+            self.schedule_bend(channel, semitones, sample);
+        }
+        self.tracks[track].bend = (to != 0).then_some(Bend {
+            channel,
+            semitones: to,
+        });
+
+        match end {
+            Some(sample) => self.schedule_note(ScheduledNote {
+                kind: NoteKind::Off,
+                channel,
+                note: held_note,
+                sample,
+            }),
+            None => {
+                let mut notes = [None; MAX_CHORD_NOTES];
+                notes[0] = Some(held_note);
+                self.tracks[track].held_notes = Some(HeldNotes { channel, notes });
+            }
+        }
+        true
     }
-}
 
-struct MyPlugin {
-    params: Arc<MyPluginParams>,
-    buffer_sample_rate: Option<f32>,
-    last_playing: bool,
-    last_pos_beats: f64,
-    searching_for_step: bool,
-}
+    // bend the notes on the channel by the given number of semitones
+    fn schedule_bend(&mut self, channel: u8, semitones: f64, sample: u64) {
+        let bend_range = self.params.bend_range.value() as f64;
+        let value = (0.5 + semitones / bend_range / 2.0).clamp(0.0, 1.0) as f32;
+        self.schedule_note(ScheduledNote {
+            kind: NoteKind::PitchBend { value },
+            channel,
+            note: 0,
+            sample,
+        });
+    }
 
-impl MyPlugin {
-    // send all notes off
-    const DEFAULT_LAST_PLAYING: bool = true;
+    // send the program of a pattern that starts playing at the given sample index of the
+    // buffer on each channel the tracks play on, ahead of the notes at the same sample
+    fn schedule_program_change(&mut self, pattern: usize, start: f64) {
+        if self.program_pattern == Some(pattern) {
+            return;
+        }
+        self.program_pattern = Some(pattern);
+
+        let Some(program) = self.patterns[pattern].program else {
+            return;
+        };
+        nih_log!("program change {program} for pattern {}", pattern + 1);
+
+        let sample = self.notes.sample_at(start);
+        let mut channels = 0u16;
+        for track in 0..NUM_TRACKS {
+            let channel = self.params.track_channel(track);
+            if channels & (1 << channel) == 0 {
+                channels |= 1 << channel;
+                self.schedule_note(ScheduledNote {
+                    kind: NoteKind::ProgramChange { program },
+                    channel,
+                    note: 0,
+                    sample,
+                });
+            }
+        }
+    }
 
-    // catch inital beat
-    const DEFAULT_LAST_POS_BEATS: f64 = -1.0;
-    const DEFAULT_SEARCHING_FOR_STEP: bool = true;
+    // notes a step sends out, snapped to the scale and moved to the key
+    fn output_notes(&self, track: usize, step: &Step) -> [Option<u8>; MAX_CHORD_NOTES] {
+        let scale = self.params.pitch_classes();
+        let root = self.params.scale_root.value() as u8;
 
-    // used in determining if play was pressed at the start of a step
-    const STEP_THRESHOLD_DIVISOR: f64 = 32.0;
+        // move to the key by the shortest distance, up to a tritone either way
+        let key = self.learned_value(LearnTarget::Key, &self.params.key);
+        let mut transpose = if key > 6 { key - 12 } else { key };
 
-    fn init(&mut self) {
-        self.last_playing = Self::DEFAULT_LAST_PLAYING;
-        self.last_pos_beats = Self::DEFAULT_LAST_POS_BEATS;
-        self.searching_for_step = Self::DEFAULT_SEARCHING_FOR_STEP;
+        // arpeggiated tracks already follow the keys played on the midi input
+        if self.params.midi_transpose.value()
+            && self.params.tracks[track].arp_mode.value() == ArpMode::Off
+        {
+            if let Some(note) = self.held_keys.last(self.params.latch.value()) {
+                transpose += note as i32 - self.params.transpose_reference.value();
+            }
+        }
+
+        let mut notes = [None; MAX_CHORD_NOTES];
+        let mut len = 0;
+        for note in step.notes().into_iter().flatten() {
+            let note = scale.quantize(note, root) as i32 + transpose;
+            // notes moved out of midi range come back by whole octaves
+            let note = match note {
+                ..=-1 => note.rem_euclid(12),
+                128.. => 116 + (note - 116).rem_euclid(12),
+                _ => note,
+            } as u8;
+            // notes of a chord can snap to the same note
+            if !notes[..len].contains(&Some(note)) {
+                notes[len] = Some(note);
+                len += 1;
+            }
+        }
+        notes
+    }
+
+    // the only note the track plays in drum mode, left as it is by the scale and key
+    fn drum_note(&self, track: usize) -> Option<u8> {
+        self.params
+            .drum_mode
+            .value()
+            .then(|| self.params.tracks[track].drum_note.value() as u8)
+    }
+
+    // velocity of one hit of a step, drawn from the step's velocity range
+    fn hit_velocity(&mut self, step: &Step) -> f32 {
+        if step.velocity_range == 0.0 {
+            return step.velocity;
+        }
+
+        let min = (step.velocity - step.velocity_range).max(0.0);
+        min + self.rng.next_f32() * (step.velocity - min)
+    }
+
+    // velocity of a hit moved randomly around the programmed velocity, never silenced
+    fn humanize_velocity(&mut self, velocity: f32) -> f32 {
+        let amount = self.params.humanize_velocity.value();
+        if amount == 0.0 {
+            return velocity;
+        }
+
+        let offset = (self.rng.next_f32() * 2.0 - 1.0) * amount;
+        (velocity + offset).clamp(Self::MIN_HUMANIZED_VELOCITY, 1.0)
+    }
+
+    // randomly move the notes of a step up together by one or more octaves
+    fn octave_jump(
+        &mut self,
+        notes: [Option<u8>; MAX_CHORD_NOTES],
+    ) -> [Option<u8>; MAX_CHORD_NOTES] {
+        if self.rng.next_f32() >= self.params.octave_jump.value() {
+            return notes;
+        }
+
+        let octave_range = self.params.octave_range.value() as u64;
+        let octaves = 1 + (self.rng.next_u64() % octave_range) as u8;
+
+        // jump less far when the highest note would leave midi range
+        let highest = notes.into_iter().flatten().max().unwrap_or(0);
+        let octaves = octaves.min((127 - highest) / 12);
+
+        notes.map(|note| note.map(|note| note + octaves * 12))
+    }
+
+    // steps nudged ahead of their boundary are scheduled during the step before them
+    fn play_early_step(&mut self, track: usize, clock: &Clock, step_start: &StepStart) {
+        let step_count = step_start.step_count + 1;
+        let Some(pattern) = self.bar_pattern(step_start.next_bar) else {
+            return;
+        };
+        let played_step = self.repeat_step(track, clock, step_count);
+
+        if self.patterns[pattern].tracks[track]
+            .step(played_step)
+            .offset
+            >= 0.0
+        {
+            return;
+        }
+
+        self.play_step(
+            track,
+            pattern,
+            played_step,
+            step_start.timing as f64 + step_start.step_samples,
+            step_start.next_step_samples,
+            step_start.straight_step_samples,
+        );
+        self.tracks[track].early_step = Some(step_count);
+    }
+
+    // start looping the current slice of the song when a repeat begins, playback
+    // carries on from wherever the song has got to when it ends
+    fn update_repeat(&mut self, clock: &Clock) {
+        if !self.params.repeat.value() && !self.repeat_held {
+            self.repeat = None;
+        } else if self.repeat.is_none() || clock.relocated || !clock.was_playing {
+            let beats = self.params.repeat_length.value().beats();
+            let pos_beats = clock.bar_aligned_beats(clock.pos_beats);
+            let start_beats = clock.pos_beats - (pos_beats - (pos_beats / beats).floor() * beats);
+            nih_log!("repeat {beats} beats from {start_beats}");
+            self.repeat = Some(Repeat { start_beats, beats });
+        }
+    }
+
+    // take up a new speed at the start of a playback or at the first bar line
+    // reached, both grids have a step boundary on the bar line so no step is cut.
+    // a bar line within the buffer leaves the steps before it at the old speed.
+    fn update_speed(&mut self, clock: &Clock) {
+        self.speed_change = None;
+        let speed = self.params.speed.value();
+        if speed == self.speed {
+            return;
+        }
+
+        let starting = !clock.was_playing || clock.relocated;
+        let bars = (clock.pos_beats - clock.bar_start_beats) / clock.bar_beats;
+        let next_bar_beats = clock.bar_start_beats + bars.ceil() * clock.bar_beats;
+        let timing = clock.timing(next_bar_beats);
+        if starting || timing <= 0 {
+            nih_log!("speed {speed:?}");
+            self.speed = speed;
+        } else if timing < clock.buffer_samples as i64 {
+            self.speed_change = Some((speed, timing as u32));
+        }
+    }
+
+    // play the steps of a track from the given one on, up to the given sample index
+    // of the buffer or to its end
+    fn play_steps(
+        &mut self,
+        track: usize,
+        clock: &Clock,
+        step_start: Option<StepStart>,
+        until: Option<u32>,
+    ) {
+        let before_until =
+            |step_start: &StepStart| until.is_none_or(|until| step_start.timing < until);
+
+        let mut next_step_start = step_start.filter(before_until);
+        while let Some(step_start) = next_step_start {
+            // switching patterns on a step boundary leaves the notes of the
+            // current step to play out, nothing plays after a stop follow action
+            let pattern = self.bar_pattern(step_start.bar);
+            if let Some(pattern) = pattern {
+                self.active_pattern = pattern;
+                self.schedule_program_change(pattern, step_start.timing as f64);
+            }
+
+            // steps nudged early were already scheduled during the previous step
+            let step_count = self.repeat_step(track, clock, step_start.step_count);
+            if pattern.is_some() && self.tracks[track].early_step != Some(step_start.step_count) {
+                self.play_step(
+                    track,
+                    self.active_pattern,
+                    step_count,
+                    step_start.timing as f64,
+                    step_start.step_samples,
+                    step_start.straight_step_samples,
+                );
+            }
+
+            let step_beats = self.step_beats(self.active_pattern, track);
+            let following = self.engine.next_step_start(step_beats, clock, &step_start);
+
+            // the step after a speed change is on the new grid, it isn't played early from this one
+            if until.is_none() || following.as_ref().is_some_and(before_until) {
+                self.play_early_step(track, clock, &step_start);
+            }
+            self.update_playhead(track, step_count);
+
+            next_step_start = following.filter(before_until);
+        }
+    }
+
+    // step played in place of the given step of a track while a repeat loops
+    fn repeat_step(&self, track: usize, clock: &Clock, step_count: i64) -> i64 {
+        let Some(repeat) = self.repeat else {
+            return step_count;
+        };
+
+        let grid = self.grid(self.active_pattern, track, clock);
+        let start = grid.pos_steps(repeat.start_beats).round() as i64;
+        let step_beats = self.step_beats(self.active_pattern, track);
+        let len = (repeat.beats / step_beats).round().max(1.0) as i64;
+        if step_count < start {
+            step_count
+        } else {
+            start + (step_count - start) % len
+        }
+    }
+
+    // end every note sounding on the channel at the given sample index of this buffer
+    fn release_channel(&mut self, channel: u8, timing: f64) {
+        self.schedule_note(ScheduledNote {
+            kind: NoteKind::Release,
+            channel,
+            note: 0,
+            sample: self.notes.sample_at(timing),
+        });
+    }
+
+    // drop the notes waiting to be sent and end every note still sounding
+    // at the start of the next buffer sent out
+    fn release_all(&mut self) {
+        self.notes.clear();
+        let sample = self.notes.sample_at(0.0);
+
+        // instruments are left unbent for whatever plays next
+        let bends: [Option<Bend>; NUM_TRACKS] =
+            std::array::from_fn(|track| self.tracks[track].bend.take());
+        for bend in bends.into_iter().flatten() {
+            self.schedule_bend(bend.channel, 0.0, sample);
+        }
+
+        // whatever plays next sends its program again
+        self.program_pattern = None;
+        self.rotation = 0;
+
+        while let Some((channel, note)) = self.sounding.pop() {
+            self.schedule_note(ScheduledNote {
+                kind: NoteKind::Off,
+                channel,
+                note,
+                sample,
+            });
+        }
+    }
+
+    // start hardware following the internal clock and send it ticks while the internal
+    // clock runs, stop it when the internal clock stops
+    fn schedule_midi_clock(&mut self, clock: Option<&Clock>) {
+        match clock.filter(|clock| clock.free_running) {
+            Some(clock) => {
+                if self.midi_clock.start() {
+                    self.schedule_clock_message(ClockMessage::Start, 0.0);
+                }
+                if self.midi_clock.enabled {
+                    let buffer_beats = clock.buffer_samples as f64 / clock.samples_per_beat;
+                    for beats in midi_clock::ticks(clock.pos_beats, buffer_beats) {
+                        self.schedule_clock_message(
+                            ClockMessage::Tick,
+                            beats * clock.samples_per_beat,
+                        );
+                    }
+                }
+            }
+            None => {
+                if self.midi_clock.stop() {
+                    self.schedule_clock_message(ClockMessage::Stop, 0.0);
+                }
+            }
+        }
+    }
+
+    // controller values of the lfo falling within this buffer
+    fn schedule_lfo(&mut self, clock: &Clock) {
+        if !self.params.lfo.enabled.value() {
+            self.lfo.reset();
+            return;
+        }
+
+        let channel = self.params.lfo.channel.value() as u8 - 1;
+        let cc = self.params.lfo.cc.value() as u8;
+        let buffer_beats = clock.buffer_samples as f64 / clock.samples_per_beat;
+        let pos_beats = clock.bar_aligned_beats(clock.pos_beats);
+        let updates = lfo::updates(
+            self.params.lfo.updates_per_beat.value(),
+            pos_beats,
+            buffer_beats,
+        );
+
+        for beats in updates {
+            if let Some(value) = self.lfo.value(&self.params.lfo, pos_beats + beats) {
+                let sample = self.notes.sample_at(beats * clock.samples_per_beat);
+                self.schedule_note(ScheduledNote {
+                    kind: NoteKind::Cc {
+                        cc,
+                        value: value as f32 / 127.0,
+                    },
+                    channel,
+                    note: 0,
+                    sample,
+                });
+            }
+        }
+    }
+
+    fn schedule_clock_message(&mut self, message: ClockMessage, timing: f64) {
+        self.schedule_note(ScheduledNote {
+            kind: NoteKind::Clock(message),
+            channel: 0,
+            note: 0,
+            sample: self.notes.sample_at(timing),
+        });
+    }
+
+    // show the step being played in the editor, while the edited steps are the ones playing
+    fn update_playhead(&self, track: usize, step_count: i64) {
+        if track != self.edit_track() {
+            return;
+        }
+
+        let step = if self.active_pattern == self.selected_pattern() {
+            self.patterns[self.active_pattern].tracks[track].step_index(step_count) as i32
+        } else {
+            editor::NO_PLAYHEAD
+        };
+        self.playhead.store(step, Ordering::Relaxed);
+    }
+
+    fn schedule_note(&mut self, note: ScheduledNote) {
+        if !self.notes.schedule(note) {
+            nih_log!("note queue full");
+            self.diagnostics.report(Diagnostic::QueueFull);
+        }
+    }
+
+    // keep track of the keys held on the midi input, and record them while
+    // the transport is running
+    fn read_input(&mut self, context: &mut impl ProcessContext<Self>, clock: Option<&Clock>) {
+        self.recorder
+            .update_step_record(self.params.step_record.value());
+
+        while let Some(event) = context.next_event() {
+            match event {
+                NoteEvent::NoteOn {
+                    timing,
+                    channel,
+                    note,
+                    velocity,
+                    ..
+                } => {
+                    // the fill and repeat notes only start the fill or repeat,
+                    // they aren't played or recorded
+                    if note as i32 == self.params.fill_note.value() {
+                        self.fill_held = true;
+                        continue;
+                    }
+                    if note as i32 == self.params.repeat_note.value() {
+                        self.repeat_held = true;
+                        continue;
+                    }
+
+                    // a split keyboard only controls the sequencer from the control side,
+                    // the other side is always played through
+                    let split = u8::try_from(self.params.split.value())
+                        .ok()
+                        .map(|split| self.params.split_zone.value().controls(split, note));
+                    let thru = match split {
+                        Some(controls) => !controls,
+                        None => {
+                            self.params.thru.value()
+                                && self.params.thru_input.accepts(channel, note)
+                        }
+                    };
+
+                    if split != Some(false) {
+                        if let Some(pattern) = self.pattern_key(note) {
+                            nih_log!("pattern key {note} picks pattern {}", pattern + 1);
+                            self.key_pattern = Some(pattern);
+                            continue;
+                        }
+                        if self.params.keys_input.accepts(channel, note) {
+                            self.held_keys.press(note);
+                        }
+                    }
+
+                    if thru {
+                        self.thru_notes.note_on(channel, note, None);
+                        let voice_id = Self::thru_voice_id(channel, note);
+                        self.schedule_thru(
+                            NoteKind::On { velocity, voice_id },
+                            channel,
+                            note,
+                            timing,
+                        );
+                    }
+
+                    if !self.params.record_input.accepts(channel, note) {
+                        continue;
+                    }
+
+                    let pattern = self.selected_pattern();
+                    let track = self.edit_track();
+                    if self.params.step_record.value() {
+                        self.recorder.step_entry(
+                            &mut self.patterns,
+                            pattern,
+                            track,
+                            note,
+                            velocity,
+                        );
+                        self.patterns_changed = true;
+                    } else if let Some(clock) = clock.filter(|_| self.params.record.value()) {
+                        let pos_steps = self.input_pos_steps(clock, timing);
+                        self.recorder.note_on(
+                            &mut self.patterns,
+                            pattern,
+                            track,
+                            note,
+                            velocity,
+                            pos_steps,
+                        );
+                        self.patterns_changed = true;
+                    }
+                }
+                NoteEvent::MidiCC { cc, value, .. } => self.control_change(cc, value),
+                NoteEvent::NoteOff {
+                    timing,
+                    channel,
+                    note,
+                    ..
+                } => {
+                    if note as i32 == self.params.fill_note.value() {
+                        self.fill_held = false;
+                        continue;
+                    }
+                    if note as i32 == self.params.repeat_note.value() {
+                        self.repeat_held = false;
+                        continue;
+                    }
+
+                    self.held_keys.release(note);
+
+                    if self.thru_notes.contains(channel, note) {
+                        self.thru_notes.note_off(channel, note);
+                        self.schedule_thru(NoteKind::Off, channel, note, timing);
+                    }
+
+                    if let Some(clock) = clock {
+                        let pos_steps = self.input_pos_steps(clock, timing);
+                        if self.recorder.note_off(&mut self.patterns, note, pos_steps) {
+                            self.patterns_changed = true;
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // pattern launched by the key, if it's one of the pattern keys
+    fn pattern_key(&self, note: u8) -> Option<usize> {
+        let first_key = u8::try_from(self.params.pattern_keys.value()).ok()?;
+        let pattern = note.checked_sub(first_key)? as usize;
+        (pattern < NUM_PATTERNS).then_some(pattern)
+    }
+
+    // a note from the midi input goes out as it was played, unmoved by humanizing
+    fn schedule_thru(&mut self, kind: NoteKind, channel: u8, note: u8, timing: u32) {
+        let note = ScheduledNote {
+            kind,
+            channel,
+            note,
+            sample: self.notes.sample_at(timing as f64),
+        };
+        if !self.notes.schedule_exact(note) {
+            nih_log!("note queue full");
+            self.diagnostics.report(Diagnostic::QueueFull);
+        }
+    }
+
+    // map the controller to the parameter being learned, and pass its value to
+    // every parameter it's mapped to
+    fn control_change(&mut self, cc: u8, value: f32) {
+        let learn = self.params.learn.value();
+        if learn != LearnTarget::Off && self.midi_learn.cc_map.cc(learn) != Some(cc) {
+            if let Ok(mut cc_map) = self.params.cc_map.try_lock() {
+                nih_log!("learn cc {cc} for {learn:?}");
+                cc_map.learn(learn, cc);
+                self.midi_learn.cc_map = *cc_map;
+            }
+        }
+
+        for target in [LearnTarget::Swing, LearnTarget::Key, LearnTarget::Pattern] {
+            if self.midi_learn.cc_map.cc(target) != Some(cc) {
+                continue;
+            }
+            let param_value = match target {
+                LearnTarget::Off => continue,
+                LearnTarget::Swing => self.params.swing.unmodulated_normalized_value(),
+                LearnTarget::Key => self.params.key.unmodulated_normalized_value(),
+                LearnTarget::Pattern => self.params.pattern.unmodulated_normalized_value(),
+            };
+            self.midi_learn.control(target, value, param_value);
+        }
+    }
+
+    // value of a parameter a controller may be mapped to, the controller
+    // sets it until the parameter itself is changed
+    fn learned_value<P: Param>(&self, target: LearnTarget, param: &P) -> P::Plain {
+        match self
+            .midi_learn
+            .value(target, param.unmodulated_normalized_value())
+        {
+            Some(value) => param.preview_plain(value),
+            None => param.modulated_plain_value(),
+        }
+    }
+
+    // position of the edited track in swung steps at the given sample index of the buffer
+    fn input_pos_steps(&self, clock: &Clock, timing: u32) -> f64 {
+        let grid = self.grid(self.selected_pattern(), self.edit_track(), clock);
+        let pos_beats = clock.pos_beats + timing as f64 / clock.samples_per_beat;
+        grid.pos_steps(pos_beats)
+    }
+
+    // velocity sent out for a note played at the given velocity. a maximum set below
+    // the minimum wins, rather than the two fighting over the velocity.
+    fn shape_velocity(&self, velocity: f32) -> f32 {
+        self.params
+            .velocity_curve
+            .value()
+            .apply(velocity)
+            .max(self.params.velocity_min.value())
+            .min(self.params.velocity_max.value())
+    }
+
+    // send the notes scheduled before the given sample index
+    fn send_notes(
+        &mut self,
+        before: u32,
+        plugin_api: PluginApi,
+        mut send: impl FnMut(NoteEvent<SysEx>),
+    ) {
+        while let Some((timing, note)) = self.notes.pop_due(before) {
+            match note.kind {
+                NoteKind::On { velocity, voice_id } => {
+                    let velocity = self.shape_velocity(velocity);
+                    self.sounding
+                        .note_on(note.channel, note.note, Some(voice_id));
+                    send(NoteEvent::NoteOn {
+                        timing,
+                        voice_id: Some(voice_id),
+                        channel: note.channel,
+                        note: note.note,
+                        velocity,
+                    });
+                }
+                NoteKind::Off => {
+                    self.sounding.note_off(note.channel, note.note);
+                    send(NoteEvent::NoteOff {
+                        timing,
+                        voice_id: self.sounding.voice_id(note.channel, note.note),
+                        channel: note.channel,
+                        note: note.note,
+                        velocity: 0.0,
+                    });
+                }
+                NoteKind::Release => {
+                    while let Some(sounding_note) = self.sounding.pop_channel(note.channel) {
+                        send(NoteEvent::NoteOff {
+                            timing,
+                            voice_id: self.sounding.voice_id(note.channel, sounding_note),
+                            channel: note.channel,
+                            note: sounding_note,
+                            velocity: 0.0,
+                        });
+                    }
+                }
+                NoteKind::Clock(message) => {
+                    send(NoteEvent::MidiSysEx {
+                        timing,
+                        message: SysEx::Clock(message),
+                    });
+                }
+                NoteKind::PolyPressure { pressure } => {
+                    send(NoteEvent::PolyPressure {
+                        timing,
+                        voice_id: self.sounding.voice_id(note.channel, note.note),
+                        channel: note.channel,
+                        note: note.note,
+                        pressure,
+                    });
+                }
+                // other plugin apis have no note expressions, channel wide messages
+                // would change every note on the channel so nothing is sent instead
+                NoteKind::Volume { .. } | NoteKind::Pan { .. } | NoteKind::Tuning { .. }
+                    if !matches!(plugin_api, PluginApi::Clap) => {}
+                NoteKind::Volume { gain } => {
+                    send(NoteEvent::PolyVolume {
+                        timing,
+                        voice_id: self.sounding.voice_id(note.channel, note.note),
+                        channel: note.channel,
+                        note: note.note,
+                        gain,
+                    });
+                }
+                NoteKind::Pan { pan } => {
+                    send(NoteEvent::PolyPan {
+                        timing,
+                        voice_id: self.sounding.voice_id(note.channel, note.note),
+                        channel: note.channel,
+                        note: note.note,
+                        pan,
+                    });
+                }
+                NoteKind::Tuning { tuning } => {
+                    send(NoteEvent::PolyTuning {
+                        timing,
+                        voice_id: self.sounding.voice_id(note.channel, note.note),
+                        channel: note.channel,
+                        note: note.note,
+                        tuning,
+                    });
+                }
+                NoteKind::Cc { cc, value } => {
+                    send(NoteEvent::MidiCC {
+                        timing,
+                        channel: note.channel,
+                        cc,
+                        value,
+                    });
+                }
+                NoteKind::ProgramChange { program } => {
+                    send(NoteEvent::MidiProgramChange {
+                        timing,
+                        channel: note.channel,
+                        program,
+                    });
+                }
+                NoteKind::PitchBend { value } => {
+                    send(NoteEvent::MidiPitchBend {
+                        timing,
+                        channel: note.channel,
+                        value,
+                    });
+                }
+            }
+        }
+    }
+
+    // every pattern plays all of its steps at the given rate on the first tracks
+    // and nothing on the others, for the benchmarks
+    #[doc(hidden)]
+    pub fn bench_pattern(&mut self, steps_per_beat: u32, tracks: usize) {
+        let rate = match steps_per_beat {
+            1 => Rate::Quarter,
+            2 => Rate::Eighth,
+            4 => Rate::Sixteenth,
+            _ => Rate::ThirtySecond,
+        };
+        for pattern in &mut self.patterns {
+            for (index, track) in pattern.tracks.iter_mut().enumerate() {
+                track.rate = rate;
+                for step in &mut track.steps {
+                    step.enabled = index < tracks;
+                }
+            }
+        }
+    }
+
+    // the playing part of process() for one buffer without a host, for the benchmarks
+    #[doc(hidden)]
+    pub fn bench_buffer(
+        &mut self,
+        snapshot: &TransportSnapshot,
+        buffer_samples: usize,
+        sample_rate: f32,
+        send: impl FnMut(PluginNoteEvent<Self>),
+    ) {
+        self.buffer_sample_rate = Some(sample_rate);
+        let clock = self
+            .engine
+            .clock(snapshot, buffer_samples, sample_rate as f64, 0.0, false);
+        self.notes.retime(clock.samples_per_beat);
+        self.update_speed(&clock);
+        self.play_buffer(&clock);
+        self.send_notes(buffer_samples as u32, PluginApi::Clap, send);
+        self.notes.advance(buffer_samples as u32);
     }
 }
 
@@ -52,10 +2984,49 @@ impl Default for MyPlugin {
         nih_log!("default constructor");
         Self {
             params: Arc::new(MyPluginParams::default()),
+            patterns: std::array::from_fn(|_| Pattern::default()),
+            active_pattern: 0,
+            launched_pattern: Some(0),
+            launched_bar: 0,
+            launch: None,
+            last_selected_pattern: 0,
+            key_pattern: None,
+            last_step_params: [Step::default(); MAX_STEPS],
+            last_num_steps: Track::DEFAULT_LEN,
+            last_rate: Track::DEFAULT_RATE,
+            last_seed: 0,
+            last_program: None,
+            last_follow: FollowAction::Off,
+            last_follow_repeats: Pattern::DEFAULT_FOLLOW_REPEATS,
+            last_follow_pattern: 0,
+            pattern_actions: PatternActions::default(),
+            patterns_changed: false,
+            incoming_pattern: Arc::new(Mutex::new(None)),
+            notes: NoteScheduler::default(),
+            sounding: SoundingNotes::default(),
+            playhead: Arc::new(AtomicI32::new(editor::NO_PLAYHEAD)),
+            diagnostics: Arc::new(Diagnostics::default()),
+            export_status: Arc::new(Mutex::new(None)),
+            program_pattern: None,
+            rng: Rng::default(),
+            action_rng: Rng::default(),
+            held_keys: HeldKeys::default(),
+            rotation: 0,
+            thru_notes: SoundingNotes::default(),
+            fill_held: false,
+            repeat_held: false,
+            repeat: None,
+            speed: Speed::Normal,
+            speed_change: None,
+            recorder: Recorder::default(),
+            tracks: Default::default(),
             buffer_sample_rate: None,
-            last_playing: Self::DEFAULT_LAST_PLAYING,
-            last_pos_beats: Self::DEFAULT_LAST_POS_BEATS,
-            searching_for_step: Self::DEFAULT_SEARCHING_FOR_STEP,
+            engine: Engine::default(),
+            free_clock: FreeClock::default(),
+            midi_clock: MidiClock::default(),
+            lfo: Lfo::default(),
+            user_groove: Groove::OFF,
+            midi_learn: MidiLearn::default(),
         }
     }
 }
@@ -65,10 +3036,22 @@ impl Plugin for MyPlugin {
         &mut self,
         _audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
         nih_log!("initialize");
         self.buffer_sample_rate = Some(buffer_config.sample_rate);
+        self.midi_clock.enabled = matches!(context.plugin_api(), PluginApi::Standalone);
+
+        // the plugin is initialized again after its state is restored
+        if let Ok(saved_patterns) = self.params.patterns.lock() {
+            self.patterns.clone_from(&saved_patterns);
+        }
+        for pattern in &mut self.patterns {
+            pattern.sanitize();
+        }
+        self.patterns_changed = false;
+        self.sync_step_params();
+        self.pattern_actions.sync(&self.params.actions);
         self.init();
         true
     }
@@ -84,140 +3067,106 @@ impl Plugin for MyPlugin {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        let transport = context.transport();
-
-        if !transport.playing {
-            if self.last_playing {
-                self.last_playing = false;
-                self.last_pos_beats = Self::DEFAULT_LAST_POS_BEATS;
-                self.searching_for_step = Self::DEFAULT_SEARCHING_FOR_STEP;
-                nih_log!("all notes off: transport pause");
-                for n in 0..=127 {
-                    context.send_event(NoteEvent::NoteOff {
-                        timing: 0,
-                        voice_id: None,
-                        channel: 0,
-                        note: n,
-                        velocity: 0.0,
-                    });
-                }
-            }
-            return ProcessStatus::Normal;
+        self.update_patterns();
+        let selected_pattern = self.selected_pattern();
+        self.patterns_changed |=
+            self.pattern_actions
+                .run(&self.params.actions, &mut self.patterns, selected_pattern);
+        self.load_incoming_pattern();
+        if let Ok(user_groove) = self.params.user_groove.try_lock() {
+            self.user_groove = *user_groove;
         }
-
-        if transport.preroll_active.unwrap_or(false) {
-            nih_log!("preroll active: do nothing");
-            return ProcessStatus::Normal;
+        if let Ok(cc_map) = self.params.cc_map.try_lock() {
+            self.midi_learn.cc_map = *cc_map;
         }
 
-        let pos_beats = match transport.pos_beats() {
-            Some(value) => value,
-            None => {
-                nih_log!("missing pos_beats");
-                return ProcessStatus::Normal;
-            }
-        };
-
-        let tempo: f64 = match transport.tempo {
-            Some(value) => value,
-            None => {
-                nih_log!("missing tempo");
-                return ProcessStatus::Normal;
-            }
-        };
+        if self.pattern_actions.reseed(&self.params.actions) {
+            let seed = (self.action_rng.next_u64() % (MyPluginParams::MAX_SEED as u64 + 1)) as u32;
+            nih_log!("reseed pattern {} with {seed}", selected_pattern + 1);
+            self.pattern_actions
+                .set_seed(&mut self.patterns, selected_pattern, seed);
+            self.patterns_changed = true;
+        }
 
-        // duration of a step in fractions of a second
-        let step_seconds = 60.0 / tempo;
+        if self.pattern_actions.randomize(&self.params.actions) {
+            self.randomize_edit_track();
+        }
 
-        // if a note on/off should be sent within this buffer,
-        // then timing is set to the buffer's sample index
-        //      corresponding to the start of the step
-        let mut timing: Option<u32> = None;
+        if self.pattern_actions.mutate(&self.params.actions) {
+            self.mutate_edit_track();
+        }
 
-        if self.searching_for_step && pos_beats.floor() > self.last_pos_beats.floor() {
-            if self.last_playing {
-                // sometimes steps begin between buffers
-                nih_log!("missed buffer containing step start, setting timing to 0");
-                timing = Some(0);
-            } else {
-                if pos_beats % 1.0 < step_seconds / Self::STEP_THRESHOLD_DIVISOR {
-                    // play was pressed at the start of a step
-                    nih_log!("initial step, setting timing to 0");
-                    timing = Some(0);
-                }
+        // the dump goes out at the start of the buffer, ahead of any notes
+        if self.pattern_actions.dump_sysex(&self.params.actions) {
+            nih_log!("sysex dump of pattern {}", selected_pattern + 1);
+            for message in sysex::dump(&self.patterns[selected_pattern], selected_pattern) {
+                context.send_event(NoteEvent::MidiSysEx { timing: 0, message });
             }
         }
 
-        self.last_playing = true;
-        self.last_pos_beats = pos_beats;
-
-        if timing == None {
-            // fraction of a beat remaining in this beat
-            let remain_beats: f64 = 1.0 - pos_beats % 1.0;
-
-            // fraction of a second remaining in this beat
-            let remain_seconds: f64 = remain_beats * step_seconds;
-
-            let buffer_samples = buffer.samples();
-
-            let buffer_sample_rate = match self.buffer_sample_rate {
-                Some(value) => value,
-                None => {
-                    nih_log!("missing buffer_sample_rate");
-                    return ProcessStatus::Normal;
-                }
-            };
-
-            // fraction of a second this buffer represents
-            let buffer_seconds: f64 = buffer_samples as f64 / buffer_sample_rate as f64;
-
-            self.searching_for_step = remain_seconds > buffer_seconds;
-
-            if self.searching_for_step {
-                // buffer does not contain a beat
-                return ProcessStatus::Normal;
-            }
+        // the file is written from the saved patterns, which are brought up to date first
+        if self.pattern_actions.export_midi(&self.params.actions) {
+            self.patterns_changed = true;
+            self.save_patterns();
+            context.execute_background(Task::ExportMidi {
+                pattern: selected_pattern,
+            });
+        }
 
-            nih_log!("buffer contains start of step");
+        let buffer_samples = buffer.samples();
+        let humanize_seconds = self.params.humanize_time.value() as f64 / 1000.0;
+        self.notes
+            .set_humanize(humanize_seconds * context.transport().sample_rate as f64);
 
-            // sample index of next beat
-            let remain_samples = (buffer_sample_rate as f64 * remain_seconds).round() as i32;
+        // the internal clock keeps running while the host's transport is stopped
+        if !context.transport().playing && !self.uses_free_clock(context.transport()) {
+            self.read_input(context, None);
+            self.diagnostics.report(Diagnostic::Stopped);
 
-            if remain_samples < 0 {
-                nih_log!("remain_samples is < 0");
-                return ProcessStatus::Normal;
+            if self.engine.stop() {
+                self.release_all();
+                self.playhead.store(editor::NO_PLAYHEAD, Ordering::Relaxed);
+                self.tracks = Default::default();
+                self.recorder.clear();
+                nih_log!("all notes off: transport pause");
             }
 
-            if remain_samples >= buffer_samples as i32 {
-                nih_log!("remain_samples is >= buffer size");
-                return ProcessStatus::Normal;
-            }
+            // notes ended by a reset are sent even while the transport is stopped
+            self.schedule_midi_clock(None);
+            let plugin_api = context.plugin_api();
+            self.send_notes(buffer_samples as u32, plugin_api, |event| {
+                context.send_event(event)
+            });
+            self.notes.advance(buffer_samples as u32);
+            self.save_patterns();
+            return ProcessStatus::Normal;
+        }
 
-            timing = Some(remain_samples as u32);
+        let clock = self.clock(context.transport(), buffer_samples);
+        self.read_input(context, clock.as_ref());
+        self.schedule_midi_clock(clock.as_ref());
+        if let Some(clock) = &clock {
+            self.schedule_lfo(clock);
+            self.update_repeat(clock);
+            self.update_speed(clock);
+            self.update_launch(clock);
         }
 
-        match timing {
-            Some(timing) => {
-                context.send_event(NoteEvent::NoteOn {
-                    timing,
-                    voice_id: None,
-                    channel: 0,
-                    note: 60,
-                    velocity: 0.8,
-                });
-                context.send_event(NoteEvent::NoteOn {
-                    timing,
-                    voice_id: None,
-                    channel: 1,
-                    note: 67,
-                    velocity: 0.8,
-                });
-            }
-            None => {
-                nih_log!("missing timing");
-            }
+        if let Some(clock) = &clock {
+            self.play_buffer(clock);
         }
 
+        let mut counters = self.engine.take_counters();
+        counters.late_notes = self.notes.take_late_notes();
+        self.diagnostics.count(counters);
+
+        let plugin_api = context.plugin_api();
+        self.send_notes(buffer_samples as u32, plugin_api, |event| {
+            context.send_event(event)
+        });
+        self.notes.advance(buffer_samples as u32);
+        self.save_patterns();
+
         ProcessStatus::Normal
     }
 
@@ -225,17 +3174,55 @@ impl Plugin for MyPlugin {
         self.params.clone()
     }
 
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        let export_status = self.export_status.clone();
+        Box::new(move |task| match task {
+            Task::ExportMidi { pattern } => {
+                let saved_pattern = match params.patterns.lock() {
+                    Ok(patterns) => patterns[pattern].clone(),
+                    Err(err) => err.into_inner()[pattern].clone(),
+                };
+                let channels = std::array::from_fn(|track| params.track_channel(track));
+                let swing_delay = (params.swing.value() as f64 - 0.5) * 2.0;
+
+                let exported = midi_file::export(&saved_pattern, pattern, channels, swing_delay);
+                let status = match exported {
+                    Ok(path) => format!("Exported pattern {} to {}", pattern + 1, path.display()),
+                    Err(err) => format!("Couldn't export pattern {}: {err}", pattern + 1),
+                };
+                nih_log!("{status}");
+                match export_status.lock() {
+                    Ok(mut export_status) => *export_status = Some(status),
+                    Err(err) => *err.into_inner() = Some(status),
+                }
+            }
+        })
+    }
+
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            self.playhead.clone(),
+            self.diagnostics.clone(),
+            self.incoming_pattern.clone(),
+            self.export_status.clone(),
+            async_executor,
+        )
+    }
+
     const NAME: &'static str = "Note Sequencer";
     const VENDOR: &'static str = "Brian Edwards";
     const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
     const EMAIL: &'static str = "brian.edwards@jalopymusic.com";
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
     const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[];
-    const MIDI_INPUT: MidiConfig = MidiConfig::None;
-    const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    // controllers, pitch bend and program changes are only sent with midi ccs enabled
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
-    type SysExMessage = ();
-    type BackgroundTask = ();
+    type SysExMessage = SysEx;
+    type BackgroundTask = Task;
 }
 
 impl ClapPlugin for MyPlugin {
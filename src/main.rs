@@ -0,0 +1,66 @@
+use nih_plug::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+use note_sequencer::MyPlugin;
+
+// settings read from standalone.json in the app's config folder, e.g.
+//
+//   { "backend": "jack", "midi_input": "system:midi_capture_1", "midi_output": "system:midi_playback_1" }
+//
+// an option given on the command line wins over the same option in the file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Config {
+    // audio and midi backend, see --help for the choices
+    backend: Option<String>,
+
+    // ports the sequencer's midi input and output are connected to
+    midi_input: Option<String>,
+    midi_output: Option<String>,
+}
+
+fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| {
+        dir.join("Jalopy Music")
+            .join("Note Sequencer")
+            .join("standalone.json")
+    })
+}
+
+// a missing file leaves everything to the command line, a broken one is reported and ignored
+fn read_config() -> Config {
+    let Some(path) = config_path() else {
+        return Config::default();
+    };
+    let Ok(json) = fs::read_to_string(&path) else {
+        return Config::default();
+    };
+
+    serde_json::from_str(&json).unwrap_or_else(|err| {
+        eprintln!("Ignoring {}: {err}", path.display());
+        Config::default()
+    })
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    let config = read_config();
+
+    for (flag, value) in [
+        ("--backend", config.backend),
+        ("--midi-input", config.midi_input),
+        ("--midi-output", config.midi_output),
+    ] {
+        let given = args
+            .iter()
+            .any(|arg| arg == flag || arg.starts_with(&format!("{flag}=")));
+        if let Some(value) = value.filter(|_| !given) {
+            args.push(flag.to_string());
+            args.push(value);
+        }
+    }
+
+    nih_export_standalone_with_args::<MyPlugin, _>(args);
+}
@@ -0,0 +1,72 @@
+use nih_plug::prelude::SysExMessage;
+
+// midi clock ticks per quarter note beat
+const TICKS_PER_BEAT: f64 = 24.0;
+
+// realtime messages sent so hardware sequencers and drum machines can follow the
+// internal clock. nih-plug only passes raw bytes out through sysex events, so the
+// single byte messages travel inside the plugin's sysex message type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockMessage {
+    Tick,
+    Start,
+    Stop,
+}
+
+impl ClockMessage {
+    const TICK: u8 = 0xf8;
+    const START: u8 = 0xfa;
+    const STOP: u8 = 0xfc;
+}
+
+impl SysExMessage for ClockMessage {
+    type Buffer = [u8; 1];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        match buffer {
+            [Self::TICK] => Some(Self::Tick),
+            [Self::START] => Some(Self::Start),
+            [Self::STOP] => Some(Self::Stop),
+            _ => None,
+        }
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        let byte = match self {
+            Self::Tick => Self::TICK,
+            Self::Start => Self::START,
+            Self::Stop => Self::STOP,
+        };
+        ([byte], 1)
+    }
+}
+
+// keeps track of whether the hardware following the internal clock was started
+#[derive(Default)]
+pub struct MidiClock {
+    // only the standalone app sends midi clock, plugin hosts keep their own
+    pub enabled: bool,
+    running: bool,
+}
+
+impl MidiClock {
+    // returns true if a start message should be sent before this buffer's ticks
+    pub fn start(&mut self) -> bool {
+        let start = self.enabled && !self.running;
+        self.running |= start;
+        start
+    }
+
+    // returns true if a stop message should be sent
+    pub fn stop(&mut self) -> bool {
+        std::mem::take(&mut self.running)
+    }
+}
+
+// distance in beats from the given position to each tick before the end of the buffer
+pub fn ticks(pos_beats: f64, buffer_beats: f64) -> impl Iterator<Item = f64> {
+    let first_tick = (pos_beats * TICKS_PER_BEAT).ceil() as i64;
+    (first_tick..)
+        .map(move |tick| tick as f64 / TICKS_PER_BEAT - pos_beats)
+        .take_while(move |&beats| beats < buffer_beats)
+}
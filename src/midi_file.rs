@@ -0,0 +1,799 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::grid::Grid;
+use crate::groove::{GrooveTemplate, MAX_GROOVE_STEPS};
+use crate::pattern::{Pattern, Step, Track, MAX_CHORD_NOTES, MAX_STEPS, NUM_TRACKS};
+use crate::rate::Rate;
+
+// ticks per quarter note beat
+pub const PPQ: u16 = 960;
+
+// exported patterns are laid out in bars of 4/4
+const BAR_BEATS: f64 = 4.0;
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const META: u8 = 0xff;
+const META_TRACK_NAME: u8 = 0x03;
+const META_END_OF_TRACK: u8 = 0x2f;
+const SYSEX: u8 = 0xf0;
+const SYSEX_ESCAPE: u8 = 0xf7;
+
+// imported notes closer than this fraction of a step to the next step are
+// treated as ending on it
+const TIE_EPSILON: f64 = 0.05;
+
+// folder in the user's data folder exported midi files are written to
+pub fn export_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| {
+        dir.join("Jalopy Music")
+            .join("Note Sequencer")
+            .join("Exports")
+    })
+}
+
+// write the given pattern slot to the export folder, returning the file written
+pub fn export(
+    pattern: &Pattern,
+    index: usize,
+    channels: [u8; NUM_TRACKS],
+    swing_delay: f64,
+) -> io::Result<PathBuf> {
+    let dir = export_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no user data folder"))?;
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!("Pattern {}.mid", index + 1));
+    fs::write(&path, write(pattern, channels, swing_delay))?;
+    Ok(path)
+}
+
+// a format 1 standard midi file with a midi track for every track of the pattern,
+// long enough for the longest track to play once and rounded up to whole bars.
+// the notes are written as programmed, before the scale and key are applied,
+// every step plays whatever its probability, and steps conditioned on a fill are
+// left out.
+pub fn write(pattern: &Pattern, channels: [u8; NUM_TRACKS], swing_delay: f64) -> Vec<u8> {
+    let len_beats = pattern
+        .tracks
+        .iter()
+        .map(|track| track.len as f64 * track.rate.beats())
+        .fold(0.0, f64::max);
+    let len_beats = ((len_beats / BAR_BEATS).ceil() * BAR_BEATS).max(BAR_BEATS);
+
+    let mut file = Vec::new();
+    file.extend_from_slice(b"MThd");
+    file.extend_from_slice(&6u32.to_be_bytes());
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&(NUM_TRACKS as u16).to_be_bytes());
+    file.extend_from_slice(&PPQ.to_be_bytes());
+
+    for (index, track) in pattern.tracks.iter().enumerate() {
+        let events = track_events(track, channels[index] & 0x0f, swing_delay, len_beats);
+        write_track(
+            &mut file,
+            &format!("Track {}", index + 1),
+            &events,
+            len_beats,
+        );
+    }
+    file
+}
+
+// a note on or off at a tick since the start of the file
+struct Event {
+    tick: u64,
+    status: u8,
+    note: u8,
+    velocity: u8,
+}
+
+fn track_events(track: &Track, channel: u8, swing_delay: f64, len_beats: f64) -> Vec<Event> {
+    let grid = Grid::new(track.rate.beats(), BAR_BEATS, swing_delay, 0.0, 0);
+    let straight_step_ticks = track.rate.beats() * PPQ as f64;
+    let end_tick = beats_to_ticks(len_beats);
+
+    let mut events = Vec::new();
+    let mut note_on = |tick: u64, note: u8, velocity: f32| {
+        let velocity = (velocity * 127.0).round().clamp(1.0, 127.0) as u8;
+        events.push(Event {
+            tick,
+            status: NOTE_ON | channel,
+            note,
+            velocity,
+        });
+    };
+    let mut offs = Vec::new();
+
+    // notes held over from the previous step because this step is tied to it
+    let mut held_notes = [None; MAX_CHORD_NOTES];
+
+    let mut step_count = 0;
+    while grid.step_pos_beats(step_count) < len_beats {
+        let step = track.step(step_count);
+        let step_start = beats_to_ticks(grid.step_pos_beats(step_count));
+        let step_ticks = grid.step_len_beats(step_count) * PPQ as f64;
+
+        // the notes of the last step are cut off at the end of the file
+        let start = (step_start as f64 + step.offset as f64 * straight_step_ticks).max(0.0);
+        let start = (start.round() as u64).min(end_tick);
+
+        let plays = step.enabled && step.condition.passes(track.iteration(step_count), false);
+        let notes = if plays {
+            step.notes()
+        } else {
+            [None; MAX_CHORD_NOTES]
+        };
+        let tied_notes = if plays && step.tie {
+            held_notes
+        } else {
+            [None; MAX_CHORD_NOTES]
+        };
+
+        // held notes this step doesn't carry on end where it starts
+        for note in held_notes.into_iter().flatten() {
+            if !tied_notes.contains(&Some(note)) || !notes.contains(&Some(note)) {
+                offs.push((start, note));
+            }
+        }
+        held_notes = [None; MAX_CHORD_NOTES];
+
+        if plays {
+            let next_step = track.step(step_count + 1);
+            let hold_last_notes = next_step.enabled && next_step.tie;
+
+            let ratchet_ticks = step_ticks / step.ratchets as f64;
+            let gate_ticks = (ratchet_ticks * step.gate as f64).round().max(1.0) as u64;
+
+            for ratchet in 0..step.ratchets {
+                let tick = (start as f64 + ratchet as f64 * ratchet_ticks).round() as u64;
+                let tick = tick.min(end_tick);
+
+                for note in notes.into_iter().flatten() {
+                    if ratchet > 0 || !tied_notes.contains(&Some(note)) {
+                        note_on(tick, note, step.velocity);
+                    }
+                }
+
+                if hold_last_notes && ratchet == step.ratchets - 1 {
+                    held_notes = notes;
+                } else {
+                    for note in notes.into_iter().flatten() {
+                        offs.push(((tick + gate_ticks).min(end_tick), note));
+                    }
+                }
+            }
+        }
+
+        step_count += 1;
+    }
+
+    for note in held_notes.into_iter().flatten() {
+        offs.push((end_tick, note));
+    }
+
+    events.extend(offs.into_iter().map(|(tick, note)| Event {
+        tick,
+        status: NOTE_OFF | channel,
+        note,
+        velocity: 0,
+    }));
+
+    // note offs go first, so a note ending where the same note starts again isn't cut short
+    events.sort_by_key(|event| (event.tick, event.status & 0xf0 == NOTE_ON));
+    events
+}
+
+fn write_track(file: &mut Vec<u8>, name: &str, events: &[Event], len_beats: f64) {
+    let mut track = Vec::new();
+
+    write_var_len(&mut track, 0);
+    track.extend_from_slice(&[META, META_TRACK_NAME]);
+    write_var_len(&mut track, name.len() as u32);
+    track.extend_from_slice(name.as_bytes());
+
+    let mut last_tick = 0;
+    for event in events {
+        write_var_len(&mut track, (event.tick - last_tick) as u32);
+        track.extend_from_slice(&[event.status, event.note, event.velocity]);
+        last_tick = event.tick;
+    }
+
+    // the track ends at the end of the last bar even when it ends in a rest
+    let end_tick = beats_to_ticks(len_beats).max(last_tick);
+    write_var_len(&mut track, (end_tick - last_tick) as u32);
+    track.extend_from_slice(&[META, META_END_OF_TRACK, 0]);
+
+    file.extend_from_slice(b"MTrk");
+    file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    file.extend_from_slice(&track);
+}
+
+// delta times and lengths are written 7 bits at a time, most significant first
+fn write_var_len(bytes: &mut Vec<u8>, value: u32) {
+    let mut shift = 28;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        bytes.push(((value >> shift) & 0x7f) as u8 | 0x80);
+        shift -= 7;
+    }
+    bytes.push((value & 0x7f) as u8);
+}
+
+fn beats_to_ticks(beats: f64) -> u64 {
+    (beats * PPQ as f64).round() as u64
+}
+
+// the notes of a midi file with every track and channel merged
+pub struct Clip {
+    // ticks per quarter note beat
+    ppq: u16,
+    notes: Vec<ClipNote>,
+}
+
+struct ClipNote {
+    start: u64,
+    len: u64,
+    note: u8,
+    velocity: u8,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// read a standard midi file of any format timed in ticks per beat
+pub fn read(bytes: &[u8]) -> io::Result<Clip> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != b"MThd" {
+        return Err(invalid("not a midi file"));
+    }
+    let header_len = reader.u32()? as usize;
+    let header = reader.take(header_len)?;
+    if header.len() < 6 {
+        return Err(invalid("midi file header too short"));
+    }
+    let ppq = u16::from_be_bytes([header[4], header[5]]);
+    if ppq & 0x8000 != 0 || ppq == 0 {
+        return Err(invalid("midi files timed in frames aren't supported"));
+    }
+
+    let mut notes = Vec::new();
+    while reader.pos < bytes.len() {
+        let chunk_type = reader.take(4)?;
+        let chunk_len = reader.u32()? as usize;
+        let chunk = reader.take(chunk_len)?;
+
+        // other chunks are left for other software
+        if chunk_type == b"MTrk" {
+            read_track(chunk, &mut notes)?;
+        }
+    }
+
+    notes.sort_by_key(|note: &ClipNote| (note.start, note.note));
+    Ok(Clip { ppq, notes })
+}
+
+fn read_track(bytes: &[u8], notes: &mut Vec<ClipNote>) -> io::Result<()> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let mut tick = 0;
+    let mut running_status = None;
+
+    // start and velocity of every sounding note on every channel
+    let mut sounding = [[None::<(u64, u8)>; 128]; 16];
+
+    let mut end_note = |sounding: &mut Option<(u64, u8)>, note: u8, tick: u64| {
+        if let Some((start, velocity)) = sounding.take() {
+            notes.push(ClipNote {
+                start,
+                len: tick - start,
+                note,
+                velocity,
+            });
+        }
+    };
+
+    while reader.pos < bytes.len() {
+        tick += reader.var_len()? as u64;
+
+        let status = match reader.peek()? {
+            status if status & 0x80 != 0 => {
+                reader.pos += 1;
+                status
+            }
+            // running status repeats the last channel message's status
+            _ => running_status.ok_or_else(|| invalid("midi data without a status"))?,
+        };
+
+        match status {
+            META => {
+                let meta_type = reader.take(1)?[0];
+                let len = reader.var_len()? as usize;
+                reader.take(len)?;
+                if meta_type == META_END_OF_TRACK {
+                    break;
+                }
+            }
+            SYSEX | SYSEX_ESCAPE => {
+                let len = reader.var_len()? as usize;
+                reader.take(len)?;
+            }
+            0xf1..=0xfe => return Err(invalid("unexpected system message in midi file")),
+            _ => {
+                running_status = Some(status);
+                let channel = (status & 0x0f) as usize;
+                let data_len = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+                let data = reader.take(data_len)?;
+
+                match (status & 0xf0, data) {
+                    (NOTE_ON, &[note, velocity]) if velocity > 0 => {
+                        let note = note & 0x7f;
+                        // a note started again ends the one already sounding
+                        end_note(&mut sounding[channel][note as usize], note, tick);
+                        sounding[channel][note as usize] = Some((tick, velocity & 0x7f));
+                    }
+                    (NOTE_ON | NOTE_OFF, &[note, _]) => {
+                        let note = note & 0x7f;
+                        end_note(&mut sounding[channel][note as usize], note, tick);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // notes left sounding end with the track
+    for (channel_notes, note) in sounding
+        .iter_mut()
+        .flat_map(|channel| channel.iter_mut().zip(0..))
+    {
+        end_note(channel_notes, note, tick);
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("midi file ends early"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> io::Result<u8> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| invalid("midi file ends early"))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn var_len(&mut self) -> io::Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.take(1)?[0];
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("midi file has a delta time that's too long"))
+    }
+}
+
+// lay the notes of a clip onto steps of the given rate. notes starting nearest
+// the same step are played together as a chord, notes held across later empty
+// steps are tied into them, and the track is long enough for the whole clip
+// rounded up to whole bars of 4/4.
+pub fn quantize(clip: &Clip, rate: Rate) -> Track {
+    let step_ticks = rate.beats() * clip.ppq as f64;
+
+    let mut track = Track {
+        steps: [Step {
+            enabled: false,
+            ..Step::default()
+        }; MAX_STEPS],
+        len: 1,
+        rate,
+    };
+
+    // length in steps of the note each step was started by
+    let mut note_steps = [0.0; MAX_STEPS];
+    let mut end_steps = 0.0_f64;
+
+    for clip_note in &clip.notes {
+        let index = (clip_note.start as f64 / step_ticks).round() as usize;
+        if index >= MAX_STEPS {
+            continue;
+        }
+
+        let len_steps = clip_note.len as f64 / step_ticks;
+        end_steps = end_steps.max(clip_note.start as f64 / step_ticks + len_steps);
+
+        let velocity = clip_note.velocity as f32 / 127.0;
+        let step = &mut track.steps[index];
+        if !step.enabled {
+            *step = Step {
+                enabled: true,
+                note: clip_note.note,
+                velocity,
+                gate: (len_steps as f32).clamp(Step::MIN_GATE, 1.0),
+                ..Step::default()
+            };
+            note_steps[index] = len_steps;
+        } else if !step.notes().contains(&Some(clip_note.note)) {
+            // a chord keeps the loudest velocity of its notes, notes past the
+            // most a step can play are left out
+            if let Some(chord_note) = step.chord_notes.iter_mut().find(|note| note.is_none()) {
+                *chord_note = Some(clip_note.note);
+                step.velocity = step.velocity.max(velocity);
+            }
+        }
+    }
+
+    // long notes are tied into the empty steps they're held across
+    for index in 0..MAX_STEPS {
+        let mut remaining = note_steps[index] - 1.0;
+        let mut next = index + 1;
+        while remaining > TIE_EPSILON && next < MAX_STEPS && !track.steps[next].enabled {
+            track.steps[next - 1].gate = 1.0;
+            track.steps[next] = Step {
+                tie: true,
+                gate: (remaining as f32).clamp(Step::MIN_GATE, 1.0),
+                ..track.steps[index]
+            };
+            remaining -= 1.0;
+            next += 1;
+        }
+    }
+
+    let steps_per_bar = ((4.0 / rate.beats()).round() as usize).max(1);
+    let len = (end_steps - TIE_EPSILON).ceil().max(1.0) as usize;
+    track.len = (len.div_ceil(steps_per_bar) * steps_per_bar).min(MAX_STEPS);
+    track
+}
+
+// the feel of a clip as a groove template repeating every bar of 4/4 or every
+// MAX_GROOVE_STEPS steps, whichever is shorter. each step of the template gets the
+// average distance of the notes nearest it from the grid, and how much louder or
+// softer they are than the clip on average. the timing is shifted so the earliest
+// step is on the grid, as grooves only ever play steps late.
+pub fn extract_groove(clip: &Clip, rate: Rate) -> GrooveTemplate {
+    let step_ticks = rate.beats() * clip.ppq as f64;
+    let steps_per_bar = ((BAR_BEATS / rate.beats()).round() as usize).max(1);
+    let len = steps_per_bar.min(MAX_GROOVE_STEPS);
+
+    let mut timing_sums = [0.0; MAX_GROOVE_STEPS];
+    let mut velocity_sums = [0.0; MAX_GROOVE_STEPS];
+    let mut counts = [0; MAX_GROOVE_STEPS];
+    for clip_note in &clip.notes {
+        let pos_steps = clip_note.start as f64 / step_ticks;
+        let step = pos_steps.round();
+        let index = step as usize % len;
+        timing_sums[index] += pos_steps - step;
+        velocity_sums[index] += clip_note.velocity as f64 / 127.0;
+        counts[index] += 1;
+    }
+
+    let num_notes = counts.iter().sum::<usize>().max(1);
+    let average_velocity = velocity_sums.iter().sum::<f64>() / num_notes as f64;
+
+    let mut template = GrooveTemplate {
+        len,
+        timing: [0.0; MAX_GROOVE_STEPS],
+        velocity: [0.0; MAX_GROOVE_STEPS],
+    };
+    for index in (0..len).filter(|&index| counts[index] > 0) {
+        template.timing[index] = (timing_sums[index] / counts[index] as f64) as f32;
+        template.velocity[index] =
+            (velocity_sums[index] / counts[index] as f64 - average_velocity) as f32;
+    }
+
+    let earliest = template.timing[..len].iter().copied().fold(0.0, f32::min);
+    for timing in &mut template.timing[..len] {
+        *timing -= earliest;
+    }
+    template
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip_note(start: u64, velocity: u8) -> ClipNote {
+        ClipNote {
+            start,
+            len: 120,
+            note: 60,
+            velocity,
+        }
+    }
+
+    fn step(note: u8) -> Step {
+        Step {
+            note,
+            ..Step::default()
+        }
+    }
+
+    #[test]
+    fn write_round_trips_ties_chords_and_offsets() {
+        // one bar of quarter note steps. the first track plays a chord, a note tied
+        // into the next step, a rest and a note pushed a quarter of a step late,
+        // the second track a single note.
+        let mut pattern = Pattern::default();
+        for track in &mut pattern.tracks {
+            track.len = 4;
+            track.steps = [Step {
+                enabled: false,
+                ..Step::default()
+            }; MAX_STEPS];
+        }
+        let steps = &mut pattern.tracks[0].steps;
+        steps[0] = Step {
+            chord_notes: [Some(64), Some(67), None, None, None],
+            ..step(60)
+        };
+        steps[1] = step(62);
+        steps[2] = Step {
+            tie: true,
+            ..step(62)
+        };
+        steps[3] = Step {
+            offset: 0.25,
+            ..step(65)
+        };
+        pattern.tracks[1].steps[0] = step(36);
+
+        let file = write(&pattern, [2, 5, 9, 15], 0.0);
+
+        // header, then the first track chunk
+        assert_eq!(&file[..14], b"MThd\0\0\0\x06\0\x01\0\x04\x03\xc0");
+        let name = b"Track 1";
+        let track_start = 14 + 8;
+        let track_len = u32::from_be_bytes(file[18..22].try_into().unwrap()) as usize;
+        let track = &file[track_start..track_start + track_len];
+        assert_eq!(
+            &track[..4 + name.len()],
+            &[&[0, META, META_TRACK_NAME, 7][..], name].concat()
+        );
+
+        // delta time, status and data of every event on channel 3, ending at the bar line
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            0x00, 0x92, 60, 102,
+            0x00, 0x92, 64, 102,
+            0x00, 0x92, 67, 102,
+            0x83, 0x60, 0x82, 60, 0,
+            0x00, 0x82, 64, 0,
+            0x00, 0x82, 67, 0,
+            0x83, 0x60, 0x92, 62, 102,
+            0x8b, 0x20, 0x82, 62, 0,
+            0x85, 0x50, 0x92, 65, 102,
+            0x83, 0x60, 0x82, 65, 0,
+            0x81, 0x70, META, META_END_OF_TRACK, 0,
+        ];
+        assert_eq!(&track[4 + name.len()..], expected);
+
+        // the second track is on its own channel
+        let track = &file[track_start + track_len + 8..];
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            0x00, 0x95, 36, 102,
+            0x83, 0x60, 0x85, 36, 0,
+            0x9a, 0x20, META, META_END_OF_TRACK, 0,
+        ];
+        assert_eq!(&track[4 + name.len()..][..expected.len()], expected);
+
+        let clip = read(&file).unwrap();
+        assert_eq!(clip.ppq, PPQ);
+        let notes: Vec<_> = clip
+            .notes
+            .iter()
+            .map(|note| (note.start, note.len, note.note, note.velocity))
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                (0, 480, 36, 102),
+                (0, 480, 60, 102),
+                (0, 480, 64, 102),
+                (0, 480, 67, 102),
+                (960, 1440, 62, 102),
+                (3120, 480, 65, 102),
+            ]
+        );
+    }
+
+    fn clip(notes: &[(u64, u64, u8, u8)]) -> Clip {
+        Clip {
+            ppq: PPQ,
+            notes: notes
+                .iter()
+                .map(|&(start, len, note, velocity)| ClipNote {
+                    start,
+                    len,
+                    note,
+                    velocity,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn quantize_rounds_to_the_nearest_step() {
+        // sixteenth note steps, 240 ticks each
+        let clip = clip(&[
+            (110, 120, 60, 100),
+            (130, 120, 62, 100),
+            (470, 120, 64, 100),
+            (1000, 120, 65, 100),
+        ]);
+        let track = quantize(&clip, Rate::Sixteenth);
+
+        let notes: Vec<_> = track.steps[..5]
+            .iter()
+            .map(|step| step.enabled.then_some(step.note))
+            .collect();
+        assert_eq!(notes, [Some(60), Some(62), Some(64), None, Some(65)]);
+        assert_eq!(track.steps[0].gate, 0.5);
+        assert!(track.steps[5..].iter().all(|step| !step.enabled));
+
+        // rounded up to a whole bar
+        assert_eq!(track.len, 16);
+    }
+
+    #[test]
+    fn quantize_merges_chords() {
+        // the last note is past the most a step can play, the repeated note
+        // is already in the chord
+        let clip = clip(&[
+            (0, 240, 60, 64),
+            (0, 240, 62, 90),
+            (10, 240, 64, 110),
+            (10, 240, 65, 80),
+            (20, 240, 67, 80),
+            (20, 240, 69, 80),
+            (20, 240, 71, 127),
+            (30, 240, 60, 120),
+        ]);
+        let track = quantize(&clip, Rate::Sixteenth);
+
+        let step = &track.steps[0];
+        assert_eq!(step.note, 60);
+        assert_eq!(
+            step.chord_notes,
+            [Some(62), Some(64), Some(65), Some(67), Some(69)]
+        );
+        assert_eq!(step.velocity, 110.0 / 127.0);
+        assert!(track.steps[1..].iter().all(|step| !step.enabled));
+    }
+
+    #[test]
+    fn quantize_ties_long_notes_across_empty_steps() {
+        // quarter note steps, 960 ticks each. the first note is held for two and
+        // a half steps, the second runs into the next note and the last only
+        // overhangs its step by less than the tie epsilon.
+        let clip = clip(&[
+            (0, 2400, 60, 100),
+            (3840, 2880, 62, 100),
+            (4800, 480, 64, 100),
+            (7680, 989, 65, 100),
+        ]);
+        let track = quantize(&clip, Rate::Quarter);
+
+        let steps = &track.steps;
+        assert!(steps[0].enabled && !steps[0].tie);
+        assert_eq!(steps[0].gate, 1.0);
+        assert!(steps[1].enabled && steps[1].tie);
+        assert_eq!((steps[1].note, steps[1].gate), (60, 1.0));
+        assert!(steps[2].enabled && steps[2].tie);
+        assert_eq!((steps[2].note, steps[2].gate), (60, 0.5));
+        assert!(!steps[3].enabled);
+
+        assert_eq!((steps[4].note, steps[4].gate), (62, 1.0));
+        assert!(steps[5].enabled && !steps[5].tie);
+        assert_eq!((steps[5].note, steps[5].gate), (64, 0.5));
+        assert!(!steps[6].enabled && !steps[7].enabled);
+
+        assert_eq!(steps[8].note, 65);
+        assert!(!steps[9].enabled);
+        assert_eq!(track.len, 12);
+    }
+
+    #[test]
+    fn quantize_drops_notes_past_max_steps() {
+        let last = (MAX_STEPS as u64 - 1) * 240;
+        let clip = clip(&[
+            (last, 120, 60, 100),
+            (last + 150, 120, 62, 100),
+            (last + 240, 120, 64, 100),
+        ]);
+        let track = quantize(&clip, Rate::Sixteenth);
+
+        assert_eq!(track.steps.iter().filter(|step| step.enabled).count(), 1);
+        assert_eq!(track.steps[MAX_STEPS - 1].note, 60);
+        assert_eq!(track.steps[MAX_STEPS - 1].chord_notes[0], None);
+        assert_eq!(track.len, MAX_STEPS);
+    }
+
+    #[test]
+    fn read_rejects_broken_files() {
+        let is_invalid = |bytes: &[u8]| {
+            read(bytes).is_err_and(|error| error.kind() == io::ErrorKind::InvalidData)
+        };
+        assert!(is_invalid(b""));
+        assert!(is_invalid(b"RIFF\0\0\0\x06\0\x01\0\x04\x03\xc0"));
+
+        let file = write(&Pattern::default(), [0; NUM_TRACKS], 0.0);
+        assert!(read(&file).is_ok());
+        assert!(is_invalid(&file[..10]));
+        assert!(is_invalid(&file[..file.len() - 1]));
+
+        // cut off anywhere, the file is read or rejected without panicking
+        for len in 0..file.len() {
+            let _ = read(&file[..len]);
+        }
+
+        // a track chunk ending in the middle of a note on
+        let mut file = file[..14].to_vec();
+        file.extend_from_slice(b"MTrk\0\0\0\x03\0\x90\x3c");
+        assert!(is_invalid(&file));
+    }
+
+    #[test]
+    fn extract_groove_averages_timing_and_velocity() {
+        // eighth note steps, 480 ticks each. the offbeats are a quarter of a step
+        // late and softer, the last note is a tenth of a step early.
+        let clip = Clip {
+            ppq: PPQ,
+            notes: vec![
+                clip_note(0, 100),
+                clip_note(600, 60),
+                clip_note(960, 100),
+                clip_note(1560, 60),
+                clip_note(1872, 80),
+            ],
+        };
+        let template = extract_groove(&clip, Rate::Eighth);
+        assert_eq!(template.len, 8);
+
+        // shifted so the early note is on the grid
+        let expected_timing = [0.1, 0.35, 0.1, 0.35, 0.0, 0.1, 0.1, 0.1];
+        let expected_velocity = [20.0, -20.0, 20.0, -20.0, 0.0, 0.0, 0.0, 0.0];
+        for index in 0..template.len {
+            assert!((template.timing[index] - expected_timing[index]).abs() < 1e-6);
+            assert!((template.velocity[index] - expected_velocity[index] / 127.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn extract_groove_fits_long_bars() {
+        let clip = Clip {
+            ppq: PPQ,
+            notes: vec![clip_note(0, 100)],
+        };
+        let template = extract_groove(&clip, Rate::ThirtySecond);
+        assert_eq!(template.len, MAX_GROOVE_STEPS);
+        assert!(template.timing.iter().all(|&timing| timing == 0.0));
+    }
+}
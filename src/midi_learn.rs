@@ -0,0 +1,87 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// number of parameters a controller can be mapped to
+pub const NUM_LEARN_TARGETS: usize = 3;
+
+// parameter the next controller moved on the midi input is mapped to
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum LearnTarget {
+    #[id = "off"]
+    #[name = "Off"]
+    Off,
+
+    #[id = "swing"]
+    #[name = "Swing"]
+    Swing,
+
+    #[id = "key"]
+    #[name = "Key"]
+    Key,
+
+    // the pattern launched, the step parameters keep editing the selected pattern
+    #[id = "pattern"]
+    #[name = "Pattern"]
+    Pattern,
+}
+
+impl LearnTarget {
+    fn index(self) -> Option<usize> {
+        match self {
+            Self::Off => None,
+            Self::Swing => Some(0),
+            Self::Key => Some(1),
+            Self::Pattern => Some(2),
+        }
+    }
+}
+
+// controller mapped to each learn target, saved with the plugin's state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CcMap {
+    ccs: [Option<u8>; NUM_LEARN_TARGETS],
+}
+
+impl CcMap {
+    pub fn learn(&mut self, target: LearnTarget, cc: u8) {
+        if let Some(index) = target.index() {
+            self.ccs[index] = Some(cc);
+        }
+    }
+
+    pub fn cc(&self, target: LearnTarget) -> Option<u8> {
+        self.ccs[target.index()?]
+    }
+}
+
+// last value a controller sent to a target, and the parameter's value at the time
+#[derive(Clone, Copy)]
+struct Control {
+    value: f32,
+    param_value: f32,
+}
+
+// mapped controllers take over their parameter until the parameter itself is
+// changed, as a plugin can't change its own parameters from the audio thread
+#[derive(Default)]
+pub struct MidiLearn {
+    pub cc_map: CcMap,
+    controls: [Option<Control>; NUM_LEARN_TARGETS],
+}
+
+impl MidiLearn {
+    // a controller value for the target, both as normalized values
+    pub fn control(&mut self, target: LearnTarget, value: f32, param_value: f32) {
+        if let Some(index) = target.index() {
+            self.controls[index] = Some(Control { value, param_value });
+        }
+    }
+
+    // normalized value the target's controller has set it to,
+    // none if the parameter has moved since
+    pub fn value(&self, target: LearnTarget, param_value: f32) -> Option<f32> {
+        let control = self.controls[target.index()?]?;
+        (control.param_value == param_value).then_some(control.value)
+    }
+}
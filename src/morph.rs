@@ -0,0 +1,32 @@
+use crate::pattern::Step;
+
+// a step part way between a step of pattern a and the same step of pattern b.
+// velocity, gate and probability are blended, a step only one of the patterns
+// plays fades in or out through its probability, and everything else is taken
+// from whichever pattern the amount is nearer to.
+pub fn morph(a: &Step, b: &Step, amount: f32) -> Step {
+    match (a.enabled, b.enabled) {
+        (true, true) => {
+            let nearer = if amount < 0.5 { a } else { b };
+            Step {
+                velocity: lerp(a.velocity, b.velocity, amount),
+                gate: lerp(a.gate, b.gate, amount),
+                probability: lerp(a.probability, b.probability, amount),
+                ..*nearer
+            }
+        }
+        (true, false) => Step {
+            probability: a.probability * (1.0 - amount),
+            ..*a
+        },
+        (false, true) => Step {
+            probability: b.probability * amount,
+            ..*b
+        },
+        (false, false) => *a,
+    }
+}
+
+fn lerp(a: f32, b: f32, amount: f32) -> f32 {
+    a + (b - a) * amount
+}
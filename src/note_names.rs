@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// names shown in place of note numbers, e.g. the sounds of a drum instrument.
+// a map is read from a json object of note numbers and names,
+//
+//   { "36": "Kick", "38": "Snare" }
+//
+// or from a text file with a note number and a name on each line,
+// blank lines and lines starting with # are skipped
+//
+//   36 Kick
+//   38 Snare
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoteNames {
+    names: BTreeMap<u8, String>,
+}
+
+impl NoteNames {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        if text.trim_start().starts_with('{') {
+            let names: BTreeMap<u8, String> = serde_json::from_str(&text)?;
+            return Ok(Self { names });
+        }
+
+        let mut names = BTreeMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (note, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let note = note.parse::<u8>().ok().filter(|&note| note < 128);
+            let name = name.trim();
+            match note {
+                Some(note) if !name.is_empty() => {
+                    names.insert(note, name.to_string());
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {}: expected a note number and a name", index + 1),
+                    ))
+                }
+            }
+        }
+        Ok(Self { names })
+    }
+
+    pub fn name(&self, note: u8) -> Option<&str> {
+        self.names.get(&note).map(String::as_str)
+    }
+
+    pub fn num_names(&self) -> usize {
+        self.names.len()
+    }
+}
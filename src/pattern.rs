@@ -0,0 +1,530 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::chord::ChordType;
+use crate::condition::TrigCondition;
+use crate::follow::FollowAction;
+use crate::rate::Rate;
+
+// maximum number of steps a pattern can hold
+pub const MAX_STEPS: usize = 64;
+
+// number of pattern slots
+pub const NUM_PATTERNS: usize = 16;
+
+// number of tracks played in parallel by each pattern
+pub const NUM_TRACKS: usize = 4;
+
+// maximum number of notes a step plays at once
+pub const MAX_CHORD_NOTES: usize = 6;
+
+// number of notes a step can pick from in place of its own note
+pub const MAX_POOL_NOTES: usize = 3;
+
+// number of controller lanes sequenced along with the notes of each track
+pub const NUM_CC_LANES: usize = 2;
+
+// triplet tracks don't fill whole bars exactly in binary
+const BAR_EPSILON: f64 = 1e-9;
+
+// fields missing from saved state, e.g. saved by an older version, take their default
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Step {
+    // disabled steps are rests
+    pub enabled: bool,
+    pub note: u8,
+    pub velocity: f32,
+
+    // each hit's velocity is drawn from this far below the velocity up to the velocity
+    pub velocity_range: f32,
+
+    // fraction of the step the note is held for
+    pub gate: f32,
+
+    // played harder by the accent amount
+    pub accent: bool,
+
+    // chance of the step playing each time it comes around
+    pub probability: f32,
+
+    // chance of a step that plays being varied, shortened or softened
+    pub variation: f32,
+
+    // loops of the track the step plays on
+    pub condition: TrigCondition,
+
+    // number of times the note is retriggered within the step
+    pub ratchets: u8,
+
+    // hold the previous step's note into this step instead of ending it,
+    // a different note on this step is played legato
+    pub tie: bool,
+
+    // bend the previous step's note to this step's note instead of playing it
+    pub slide: bool,
+
+    // fraction of a step the step is pushed later, or pulled earlier when negative
+    pub offset: f32,
+
+    // chord built on the step's note
+    pub chord_type: ChordType,
+
+    // notes played along with the step's note
+    pub chord_notes: [Option<u8>; MAX_CHORD_NOTES - 1],
+
+    // notes picked at random in place of the step's note each time it plays
+    pub pool: [Option<PoolNote>; MAX_POOL_NOTES],
+
+    // controller value sent for each cc lane when the step comes around
+    pub cc: [Option<u8>; NUM_CC_LANES],
+
+    // polyphonic aftertouch sent just after each of the step's notes starts
+    pub pressure: Option<u8>,
+
+    // note expressions of the step's notes, only sent to clap hosts.
+    // volume is in decibels and tuning in semitones.
+    pub volume: f32,
+    pub pan: f32,
+    pub tuning: f32,
+}
+
+impl Step {
+    pub const DEFAULT_ENABLED: bool = true;
+    pub const DEFAULT_NOTE: u8 = 60;
+    pub const DEFAULT_VELOCITY: f32 = 0.8;
+    pub const DEFAULT_VELOCITY_RANGE: f32 = 0.0;
+    pub const DEFAULT_GATE: f32 = 0.5;
+    pub const MIN_GATE: f32 = 0.01;
+    pub const DEFAULT_ACCENT: bool = false;
+    pub const DEFAULT_PROBABILITY: f32 = 1.0;
+    pub const DEFAULT_VARIATION: f32 = 0.0;
+    pub const DEFAULT_CONDITION: TrigCondition = TrigCondition::Always;
+    pub const DEFAULT_RATCHETS: u8 = 1;
+    pub const MAX_RATCHETS: u8 = 8;
+    pub const DEFAULT_TIE: bool = false;
+    pub const DEFAULT_SLIDE: bool = false;
+    pub const DEFAULT_OFFSET: f32 = 0.0;
+    pub const MAX_OFFSET: f32 = 0.5;
+    pub const DEFAULT_CHORD_TYPE: ChordType = ChordType::None;
+    pub const DEFAULT_CHORD_NOTES: [Option<u8>; MAX_CHORD_NOTES - 1] = [None; MAX_CHORD_NOTES - 1];
+    pub const DEFAULT_POOL: [Option<PoolNote>; MAX_POOL_NOTES] = [None; MAX_POOL_NOTES];
+    pub const DEFAULT_CC: [Option<u8>; NUM_CC_LANES] = [None; NUM_CC_LANES];
+    pub const DEFAULT_PRESSURE: Option<u8> = None;
+    pub const DEFAULT_VOLUME: f32 = 0.0;
+    pub const MIN_VOLUME: f32 = -24.0;
+    pub const MAX_VOLUME: f32 = 12.0;
+    pub const DEFAULT_PAN: f32 = 0.0;
+    pub const DEFAULT_TUNING: f32 = 0.0;
+    pub const MAX_TUNING: f32 = 12.0;
+
+    // every note the step plays, without repeats, with the chord voiced
+    // above the step's note followed by the extra chord notes
+    pub fn notes(&self) -> [Option<u8>; MAX_CHORD_NOTES] {
+        let chord = self
+            .chord_type
+            .intervals()
+            .iter()
+            .map(|interval| self.note + interval)
+            .filter(|&note| note <= 127);
+        let chord_notes = self.chord_notes.iter().flatten().copied();
+
+        let mut notes = [None; MAX_CHORD_NOTES];
+        let mut len = 0;
+        for note in std::iter::once(self.note).chain(chord).chain(chord_notes) {
+            if len == MAX_CHORD_NOTES {
+                break;
+            }
+            if !notes[..len].contains(&Some(note)) {
+                notes[len] = Some(note);
+                len += 1;
+            }
+        }
+        notes
+    }
+
+    // the step's note or one of its pool notes, chosen by a random number between
+    // 0 and 1 in proportion to their weights. the step's own note weighs 1.
+    pub fn pick_note(&self, random: f32) -> u8 {
+        let pool = self.pool.iter().flatten();
+        let total_weight = 1.0 + pool.clone().map(|pool_note| pool_note.weight).sum::<f32>();
+
+        let mut remaining = random * total_weight - 1.0;
+        for pool_note in pool {
+            if remaining < 0.0 {
+                break;
+            }
+            if remaining < pool_note.weight {
+                return pool_note.note;
+            }
+            remaining -= pool_note.weight;
+        }
+        self.note
+    }
+
+    // copy the fields that differ between two versions of a step,
+    // leaving the rest of this step as it is
+    pub fn apply_changes(&mut self, old: &Step, new: &Step) {
+        macro_rules! apply_changes {
+            ($($field:ident),*) => {
+                $(
+                    if old.$field != new.$field {
+                        self.$field = new.$field;
+                    }
+                )*
+            };
+        }
+
+        apply_changes!(
+            enabled,
+            note,
+            velocity,
+            velocity_range,
+            gate,
+            accent,
+            probability,
+            variation,
+            condition,
+            ratchets,
+            tie,
+            slide,
+            offset,
+            chord_type,
+            pressure,
+            volume,
+            pan,
+            tuning
+        );
+
+        // chord notes are edited one at a time
+        for ((chord_note, old), new) in self
+            .chord_notes
+            .iter_mut()
+            .zip(old.chord_notes)
+            .zip(new.chord_notes)
+        {
+            if old != new {
+                *chord_note = new;
+            }
+        }
+
+        for ((pool_note, old), new) in self.pool.iter_mut().zip(old.pool).zip(new.pool) {
+            if old != new {
+                *pool_note = new;
+            }
+        }
+
+        for ((cc, old), new) in self.cc.iter_mut().zip(old.cc).zip(new.cc) {
+            if old != new {
+                *cc = new;
+            }
+        }
+    }
+
+    // bring values from a hand-edited or corrupt file back into range
+    pub fn sanitize(&mut self) {
+        self.note = self.note.min(127);
+        self.ratchets = self.ratchets.clamp(1, Self::MAX_RATCHETS);
+        for value in self.chord_notes.iter_mut().chain(&mut self.cc).flatten() {
+            *value = (*value).min(127);
+        }
+        for pool_note in self.pool.iter_mut().flatten() {
+            pool_note.note = pool_note.note.min(127);
+            pool_note.weight = clamp_or(pool_note.weight, 0.0, 1.0, PoolNote::DEFAULT_WEIGHT);
+        }
+        if let Some(pressure) = &mut self.pressure {
+            *pressure = (*pressure).min(127);
+        }
+
+        // the same ranges as the step parameters
+        self.velocity = clamp_or(self.velocity, 0.0, 1.0, Self::DEFAULT_VELOCITY);
+        self.velocity_range = clamp_or(self.velocity_range, 0.0, 1.0, Self::DEFAULT_VELOCITY_RANGE);
+        self.gate = clamp_or(self.gate, Self::MIN_GATE, 1.0, Self::DEFAULT_GATE);
+        self.probability = clamp_or(self.probability, 0.0, 1.0, Self::DEFAULT_PROBABILITY);
+        self.variation = clamp_or(self.variation, 0.0, 1.0, Self::DEFAULT_VARIATION);
+        self.offset = clamp_or(
+            self.offset,
+            -Self::MAX_OFFSET,
+            Self::MAX_OFFSET,
+            Self::DEFAULT_OFFSET,
+        );
+        self.volume = clamp_or(
+            self.volume,
+            Self::MIN_VOLUME,
+            Self::MAX_VOLUME,
+            Self::DEFAULT_VOLUME,
+        );
+        self.pan = clamp_or(self.pan, -1.0, 1.0, Self::DEFAULT_PAN);
+        self.tuning = clamp_or(
+            self.tuning,
+            -Self::MAX_TUNING,
+            Self::MAX_TUNING,
+            Self::DEFAULT_TUNING,
+        );
+    }
+}
+
+// a value clamped to its range, or the default when it isn't a number
+fn clamp_or(value: f32, min: f32, max: f32, default: f32) -> f32 {
+    if value.is_nan() {
+        default
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self {
+            enabled: Self::DEFAULT_ENABLED,
+            note: Self::DEFAULT_NOTE,
+            velocity: Self::DEFAULT_VELOCITY,
+            velocity_range: Self::DEFAULT_VELOCITY_RANGE,
+            gate: Self::DEFAULT_GATE,
+            accent: Self::DEFAULT_ACCENT,
+            probability: Self::DEFAULT_PROBABILITY,
+            variation: Self::DEFAULT_VARIATION,
+            condition: Self::DEFAULT_CONDITION,
+            ratchets: Self::DEFAULT_RATCHETS,
+            tie: Self::DEFAULT_TIE,
+            slide: Self::DEFAULT_SLIDE,
+            offset: Self::DEFAULT_OFFSET,
+            chord_type: Self::DEFAULT_CHORD_TYPE,
+            chord_notes: Self::DEFAULT_CHORD_NOTES,
+            pool: Self::DEFAULT_POOL,
+            cc: Self::DEFAULT_CC,
+            pressure: Self::DEFAULT_PRESSURE,
+            volume: Self::DEFAULT_VOLUME,
+            pan: Self::DEFAULT_PAN,
+            tuning: Self::DEFAULT_TUNING,
+        }
+    }
+}
+
+// a note a step can play in place of its own, weight is its chance
+// of being picked relative to the other notes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PoolNote {
+    pub note: u8,
+    pub weight: f32,
+}
+
+impl PoolNote {
+    pub const DEFAULT_WEIGHT: f32 = 1.0;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Track {
+    #[serde(with = "steps_serde")]
+    pub steps: [Step; MAX_STEPS],
+
+    // number of steps played before the track loops,
+    // tracks of different lengths drift against each other
+    pub len: usize,
+
+    // duration of each step, tracks at different rates play polyrhythms
+    pub rate: Rate,
+}
+
+impl Track {
+    pub const DEFAULT_LEN: usize = 16;
+    pub const DEFAULT_RATE: Rate = Rate::Quarter;
+
+    // index of the step played at the given step count since the start of the song,
+    // wrapping around the track length
+    pub fn step_index(&self, step_count: i64) -> usize {
+        step_count.rem_euclid(self.len.max(1) as i64) as usize
+    }
+
+    // number of times the track has looped by the given step count
+    pub fn iteration(&self, step_count: i64) -> i64 {
+        step_count.div_euclid(self.len.max(1) as i64)
+    }
+
+    // number of times the track has looped between the given step counts,
+    // the loop the start falls in counts as the first
+    pub fn iteration_since(&self, step_count: i64, start: i64) -> i64 {
+        self.iteration(step_count) - self.iteration(start)
+    }
+
+    pub fn step(&self, step_count: i64) -> &Step {
+        &self.steps[self.step_index(step_count)]
+    }
+
+    pub fn sanitize(&mut self) {
+        self.len = self.len.clamp(1, MAX_STEPS);
+        for step in &mut self.steps {
+            step.sanitize();
+        }
+    }
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            steps: [Step::default(); MAX_STEPS],
+            len: Self::DEFAULT_LEN,
+            rate: Self::DEFAULT_RATE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pattern {
+    pub tracks: [Track; NUM_TRACKS],
+
+    // seed of the random choices made while the pattern plays
+    pub seed: u32,
+
+    // program change sent on every track's channel when the pattern starts playing
+    pub program: Option<u8>,
+
+    // pattern launched after this one has played through follow_repeats times,
+    // follow_pattern is the slot played by the pattern follow action
+    pub follow: FollowAction,
+    pub follow_repeats: u32,
+    pub follow_pattern: usize,
+}
+
+impl Pattern {
+    pub const DEFAULT_FOLLOW_REPEATS: u32 = 1;
+
+    // a pattern that hasn't been edited
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // number of whole bars the longest track takes to play through
+    pub fn bars(&self, bar_beats: f64) -> i64 {
+        let beats = self
+            .tracks
+            .iter()
+            .map(|track| track.len.max(1) as f64 * track.rate.beats())
+            .fold(0.0, f64::max);
+        ((beats / bar_beats - BAR_EPSILON).ceil() as i64).max(1)
+    }
+
+    // patterns read from files or saved state are checked before they reach
+    // the audio thread, which indexes and shifts by these values
+    pub fn sanitize(&mut self) {
+        for track in &mut self.tracks {
+            track.sanitize();
+        }
+        self.program = self.program.map(|program| program.min(127));
+        self.follow_pattern = self.follow_pattern.min(NUM_PATTERNS - 1);
+    }
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Self {
+            tracks: std::array::from_fn(|_| Track::default()),
+            seed: 0,
+            program: None,
+            follow: FollowAction::Off,
+            follow_repeats: Self::DEFAULT_FOLLOW_REPEATS,
+            follow_pattern: 0,
+        }
+    }
+}
+
+// serde only handles arrays of up to 32 elements, so steps are saved as a list
+mod steps_serde {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        steps: &[Step; MAX_STEPS],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        steps.as_slice().serialize(serializer)
+    }
+
+    // a shorter list leaves the remaining steps at their default
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[Step; MAX_STEPS], D::Error> {
+        let mut steps = [Step::default(); MAX_STEPS];
+        for (step, saved) in steps
+            .iter_mut()
+            .zip(Vec::<Step>::deserialize(deserializer)?)
+        {
+            *step = saved;
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a project saved with broken pattern state still plays within range once restored
+    #[test]
+    fn sanitize_restores_saved_floats_to_range() {
+        let mut pattern = Pattern::default();
+        let step = &mut pattern.tracks[2].steps[5];
+        step.gate = 8.0;
+        step.offset = -3.0;
+        step.probability = f32::NAN;
+        step.velocity = f32::INFINITY;
+        step.tuning = f32::NAN;
+        step.pool[1] = Some(PoolNote {
+            note: 64,
+            weight: f32::NAN,
+        });
+        pattern.tracks[2].len = MAX_STEPS + 1;
+
+        pattern.sanitize();
+
+        let track = &pattern.tracks[2];
+        let step = track.steps[5];
+        assert_eq!(track.len, MAX_STEPS);
+        assert_eq!(step.gate, 1.0);
+        assert_eq!(step.offset, -Step::MAX_OFFSET);
+        assert_eq!(step.probability, Step::DEFAULT_PROBABILITY);
+        assert_eq!(step.velocity, 1.0);
+        assert_eq!(step.tuning, Step::DEFAULT_TUNING);
+        assert_eq!(
+            step.pool[1].map(|pool_note| pool_note.weight),
+            Some(PoolNote::DEFAULT_WEIGHT)
+        );
+    }
+
+    // a pattern switched in on an odd loop of the song still starts on its first loop
+    #[test]
+    fn trig_conditions_count_loops_from_pattern_start() {
+        let track = Track::default();
+        let switch = Track::DEFAULT_LEN as i64;
+
+        let first_pass = track.iteration_since(switch, switch);
+        assert_eq!(first_pass, 0);
+        assert!(TrigCondition::OneOfTwo.passes(first_pass, false));
+        assert!(TrigCondition::First.passes(first_pass, false));
+
+        let second_pass = track.iteration_since(switch * 2 + 3, switch);
+        assert_eq!(second_pass, 1);
+        assert!(!TrigCondition::OneOfTwo.passes(second_pass, false));
+        assert!(TrigCondition::TwoOfTwo.passes(second_pass, false));
+    }
+
+    // launched part way through a loop, the partial loop is the first pass
+    #[test]
+    fn iteration_since_counts_track_wraps() {
+        let track = Track::default();
+        assert_eq!(track.iteration_since(20, 20), 0);
+        assert_eq!(track.iteration_since(31, 20), 0);
+        assert_eq!(track.iteration_since(32, 20), 1);
+    }
+
+    #[test]
+    fn sanitize_keeps_steps_in_range() {
+        let mut pattern = Pattern::default();
+        pattern.tracks[0].steps[0].offset = 0.25;
+        pattern.tracks[0].steps[0].gate = 0.75;
+        let saved = pattern.clone();
+
+        pattern.sanitize();
+        assert_eq!(pattern, saved);
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::pattern::Pattern;
+use crate::scale::{PitchClasses, Scale};
+
+const PRESET_EXTENSION: &str = "json";
+
+// a pattern saved under a name along with the settings it was written for,
+// so it can be loaded into any pattern slot of another project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub pattern: Pattern,
+    pub settings: PresetSettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetSettings {
+    pub swing: f32,
+    pub scale: Scale,
+    pub scale_root: i32,
+    pub custom_scale: u16,
+    pub key: i32,
+}
+
+impl Default for PresetSettings {
+    fn default() -> Self {
+        Self {
+            swing: 0.5,
+            scale: Scale::Chromatic,
+            scale_root: 0,
+            custom_scale: PitchClasses::DEFAULT_CUSTOM_MASK,
+            key: 0,
+        }
+    }
+}
+
+// folder in the user's data folder the presets are kept in
+pub fn preset_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| {
+        dir.join("Jalopy Music")
+            .join("Note Sequencer")
+            .join("Presets")
+    })
+}
+
+// names of the saved presets in alphabetical order
+pub fn list() -> io::Result<Vec<String>> {
+    let Some(dir) = preset_dir() else {
+        return Ok(Vec::new());
+    };
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // nothing has been saved yet
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == PRESET_EXTENSION)
+        {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort_by_key(|name| name.to_lowercase());
+    Ok(names)
+}
+
+pub fn save(name: &str, preset: &Preset) -> io::Result<()> {
+    let path = preset_path(name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let json = serde_json::to_string_pretty(preset)?;
+    fs::write(path, json)
+}
+
+pub fn load(name: &str) -> io::Result<Preset> {
+    let json = fs::read_to_string(preset_path(name)?)?;
+    let mut preset: Preset = serde_json::from_str(&json)?;
+    preset.pattern.sanitize();
+    Ok(preset)
+}
+
+// a single pattern written as indented json, to keep in version control,
+// edit by hand or pass on to other users. steps left out of a file read back
+// take their default.
+pub fn export_pattern(path: &Path, pattern: &Pattern) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(pattern)?;
+    fs::write(path, json)
+}
+
+pub fn import_pattern(path: &Path) -> io::Result<Pattern> {
+    let json = fs::read_to_string(path)?;
+    let mut pattern: Pattern = serde_json::from_str(&json)?;
+    pattern.sanitize();
+    Ok(pattern)
+}
+
+// names are used as file names, so they can't lead out of the preset folder
+fn preset_path(name: &str) -> io::Result<PathBuf> {
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid preset name \"{name}\""),
+        ));
+    }
+
+    let dir = preset_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no user data folder"))?;
+    Ok(dir.join(format!("{name}.{PRESET_EXTENSION}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::{PoolNote, Step, MAX_STEPS, NUM_PATTERNS};
+
+    #[test]
+    fn import_clamps_out_of_range_values() {
+        let path = std::env::temp_dir().join("note-sequencer-out-of-range.json");
+        fs::write(
+            &path,
+            r#"{
+                "tracks": [
+                    {
+                        "len": 200,
+                        "steps": [{
+                            "note": 200,
+                            "ratchets": 0,
+                            "chord_notes": [130, null, null, null, 255],
+                            "pool": [{ "note": 255, "weight": -3.0 }, null, null],
+                            "cc": [128, 64],
+                            "pressure": 200,
+                            "velocity": 2.5,
+                            "velocity_range": 7.0,
+                            "gate": -1.0,
+                            "probability": -0.5,
+                            "variation": 4.0,
+                            "offset": 1e6,
+                            "volume": 100.0,
+                            "pan": -9.0,
+                            "tuning": 1e9
+                        }]
+                    },
+                    { "len": 0 },
+                    {},
+                    {}
+                ],
+                "program": 200,
+                "follow_pattern": 99
+            }"#,
+        )
+        .unwrap();
+        let pattern = import_pattern(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(pattern.tracks[0].len, MAX_STEPS);
+        assert_eq!(pattern.tracks[1].len, 1);
+        assert_eq!(pattern.program, Some(127));
+        assert_eq!(pattern.follow_pattern, NUM_PATTERNS - 1);
+
+        let step = pattern.tracks[0].steps[0];
+        assert_eq!(step.note, 127);
+        assert_eq!(step.ratchets, 1);
+        assert_eq!(step.chord_notes, [Some(127), None, None, None, Some(127)]);
+        assert_eq!(
+            step.pool[0],
+            Some(PoolNote {
+                note: 127,
+                weight: 0.0
+            })
+        );
+        assert_eq!(step.cc, [Some(127), Some(64)]);
+        assert_eq!(step.pressure, Some(127));
+        assert_eq!(step.velocity, 1.0);
+        assert_eq!(step.velocity_range, 1.0);
+        assert_eq!(step.gate, Step::MIN_GATE);
+        assert_eq!(step.probability, 0.0);
+        assert_eq!(step.variation, 1.0);
+        assert_eq!(step.offset, Step::MAX_OFFSET);
+        assert_eq!(step.volume, Step::MAX_VOLUME);
+        assert_eq!(step.pan, -1.0);
+        assert_eq!(step.tuning, Step::MAX_TUNING);
+
+        // every step can be played without going out of bounds
+        for step_count in 0..MAX_STEPS as i64 * 2 {
+            let _: &Step = pattern.tracks[0].step(step_count);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// note value of a single step
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Rate {
+    #[id = "1"]
+    #[name = "1/1"]
+    Whole,
+
+    #[id = "2"]
+    #[name = "1/2"]
+    Half,
+
+    #[id = "4"]
+    #[name = "1/4"]
+    Quarter,
+
+    #[id = "8d"]
+    #[name = "1/8."]
+    DottedEighth,
+
+    #[id = "8"]
+    #[name = "1/8"]
+    Eighth,
+
+    #[id = "8t"]
+    #[name = "1/8T"]
+    EighthTriplet,
+
+    #[id = "16d"]
+    #[name = "1/16."]
+    DottedSixteenth,
+
+    #[id = "16"]
+    #[name = "1/16"]
+    Sixteenth,
+
+    #[id = "16t"]
+    #[name = "1/16T"]
+    SixteenthTriplet,
+
+    #[id = "32"]
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl Rate {
+    // duration of a step in quarter note beats
+    pub fn beats(self) -> f64 {
+        match self {
+            Rate::Whole => 4.0,
+            Rate::Half => 2.0,
+            Rate::Quarter => 1.0,
+            Rate::DottedEighth => 0.75,
+            Rate::Eighth => 0.5,
+            Rate::EighthTriplet => 1.0 / 3.0,
+            Rate::DottedSixteenth => 0.375,
+            Rate::Sixteenth => 0.25,
+            Rate::SixteenthTriplet => 1.0 / 6.0,
+            Rate::ThirtySecond => 0.125,
+        }
+    }
+}
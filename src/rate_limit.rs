@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// lets through at most a fixed number of log messages a second, so messages logged
+// every buffer don't flood the log. the messages held back are counted, and the
+// count is handed to the next message let through.
+pub struct RateLimiter {
+    // start of the current second in milliseconds since the epoch
+    window_start: AtomicU64,
+    sent: AtomicU32,
+    dropped: AtomicU32,
+}
+
+impl RateLimiter {
+    const WINDOW_MS: u64 = 1000;
+    const MAX_MESSAGES: u32 = 20;
+
+    pub const fn new() -> Self {
+        Self {
+            window_start: AtomicU64::new(0),
+            sent: AtomicU32::new(0),
+            dropped: AtomicU32::new(0),
+        }
+    }
+
+    // the number of messages held back since the last one let through,
+    // or none if this message is held back too
+    pub fn allow(&self) -> Option<u32> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+
+        // the first message of a new second starts the count over
+        let window_start = self.window_start.load(Ordering::Relaxed);
+        if now.saturating_sub(window_start) >= Self::WINDOW_MS
+            && self
+                .window_start
+                .compare_exchange(window_start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.sent.store(0, Ordering::Relaxed);
+        }
+
+        if self.sent.fetch_add(1, Ordering::Relaxed) < Self::MAX_MESSAGES {
+            Some(self.dropped.swap(0, Ordering::Relaxed))
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::pattern::{Pattern, Step, NUM_PATTERNS};
+use crate::trigger::Trigger;
+
+// maximum number of notes held at once while recording
+const MAX_HELD: usize = 16;
+
+#[derive(Clone, Copy)]
+struct HeldNote {
+    note: u8,
+    pattern: usize,
+    track: usize,
+    step: usize,
+
+    // position of the note on in steps
+    pos_steps: f64,
+}
+
+// writes notes played on the midi input into the step nearest to them,
+// the gate is set once the note is released
+#[derive(Default)]
+pub struct Recorder {
+    held: [Option<HeldNote>; MAX_HELD],
+
+    // step filled by the next note in step record mode
+    cursor: usize,
+    step_record: Trigger,
+}
+
+impl Recorder {
+    pub fn note_on(
+        &mut self,
+        patterns: &mut [Pattern; NUM_PATTERNS],
+        pattern: usize,
+        track: usize,
+        note: u8,
+        velocity: f32,
+        pos_steps: f64,
+    ) {
+        let recorded_track = &mut patterns[pattern].tracks[track];
+        let step = recorded_track.step_index(pos_steps.round() as i64);
+
+        nih_log!("record note {} at step {}", note, step + 1);
+        let recorded_step = &mut recorded_track.steps[step];
+        recorded_step.enabled = true;
+        recorded_step.note = note;
+        recorded_step.velocity = velocity;
+
+        if let Some(slot) = self.held.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(HeldNote {
+                note,
+                pattern,
+                track,
+                step,
+                pos_steps,
+            });
+        }
+    }
+
+    // returns whether the note was being recorded and its step's gate was set
+    pub fn note_off(
+        &mut self,
+        patterns: &mut [Pattern; NUM_PATTERNS],
+        note: u8,
+        pos_steps: f64,
+    ) -> bool {
+        let held = self
+            .held
+            .iter_mut()
+            .find(|slot| matches!(slot, Some(held) if held.note == note))
+            .and_then(|slot| slot.take());
+
+        let Some(held) = held else {
+            return false;
+        };
+        let gate = (pos_steps - held.pos_steps) as f32;
+        patterns[held.pattern].tracks[held.track].steps[held.step].gate =
+            gate.clamp(Step::MIN_GATE, 1.0);
+        true
+    }
+
+    // the cursor goes back to the first step whenever step record is switched on
+    pub fn update_step_record(&mut self, enabled: bool) {
+        if self.step_record.fired(enabled) {
+            self.cursor = 0;
+        }
+    }
+
+    // fill the step under the cursor and move on to the next step
+    pub fn step_entry(
+        &mut self,
+        patterns: &mut [Pattern; NUM_PATTERNS],
+        pattern: usize,
+        track: usize,
+        note: u8,
+        velocity: f32,
+    ) {
+        let recorded_track = &mut patterns[pattern].tracks[track];
+        let step = recorded_track.step_index(self.cursor as i64);
+
+        nih_log!("step record note {} at step {}", note, step + 1);
+        let recorded_step = &mut recorded_track.steps[step];
+        recorded_step.enabled = true;
+        recorded_step.note = note;
+        recorded_step.velocity = velocity;
+
+        self.cursor = recorded_track.step_index(step as i64 + 1);
+    }
+
+    // forget the held notes, leaving the gates they were recorded with
+    pub fn clear(&mut self) {
+        self.held = [None; MAX_HELD];
+    }
+}
@@ -0,0 +1,190 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// names of the twelve pitch classes starting from c
+pub const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+type ValueToString = Arc<dyn Fn(i32) -> String + Send + Sync>;
+type StringToValue = Arc<dyn Fn(&str) -> Option<i32> + Send + Sync>;
+
+// formatters showing a pitch class as its note name
+pub fn v2s_pitch_class() -> ValueToString {
+    Arc::new(|value| PITCH_CLASS_NAMES[value.rem_euclid(12) as usize].to_string())
+}
+
+pub fn s2v_pitch_class() -> StringToValue {
+    Arc::new(|string| {
+        PITCH_CLASS_NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(string.trim()))
+            .map(|index| index as i32)
+    })
+}
+
+// formatters showing a custom scale's mask as the degrees in it, counted in
+// semitones above the root, e.g. "0 2 4 5 7 9 11"
+pub fn v2s_pitch_class_mask() -> ValueToString {
+    Arc::new(|value| {
+        let degrees: Vec<String> = PitchClasses::from_mask(value as u16)
+            .degrees()
+            .map(|degree| degree.to_string())
+            .collect();
+        degrees.join(" ")
+    })
+}
+
+pub fn s2v_pitch_class_mask() -> StringToValue {
+    Arc::new(|string| {
+        string
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|degree| !degree.is_empty())
+            .try_fold(0, |mask, degree| match degree.parse::<u8>() {
+                Ok(degree) if degree < 12 => Some(mask | 1 << degree),
+                _ => None,
+            })
+    })
+}
+
+// scale emitted notes are snapped to
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Scale {
+    #[id = "chromatic"]
+    #[name = "Chromatic"]
+    Chromatic,
+
+    #[id = "major"]
+    #[name = "Major"]
+    Major,
+
+    #[id = "minor"]
+    #[name = "Minor"]
+    Minor,
+
+    #[id = "harmonic_minor"]
+    #[name = "Harmonic Minor"]
+    HarmonicMinor,
+
+    #[id = "dorian"]
+    #[name = "Dorian"]
+    Dorian,
+
+    #[id = "phrygian"]
+    #[name = "Phrygian"]
+    Phrygian,
+
+    #[id = "lydian"]
+    #[name = "Lydian"]
+    Lydian,
+
+    #[id = "mixolydian"]
+    #[name = "Mixolydian"]
+    Mixolydian,
+
+    #[id = "locrian"]
+    #[name = "Locrian"]
+    Locrian,
+
+    #[id = "major_pentatonic"]
+    #[name = "Major Pentatonic"]
+    MajorPentatonic,
+
+    #[id = "minor_pentatonic"]
+    #[name = "Minor Pentatonic"]
+    MinorPentatonic,
+
+    #[id = "blues"]
+    #[name = "Blues"]
+    Blues,
+
+    // the degrees of the custom scale mask
+    #[id = "custom"]
+    #[name = "Custom"]
+    Custom,
+}
+
+impl Scale {
+    // semitones above the root of each degree of the scale,
+    // a custom scale's degrees come from its mask instead
+    fn degrees(self) -> &'static [u8] {
+        match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+            Scale::Custom => &[],
+        }
+    }
+
+    // the degrees of the scale, with the given mask used for a custom scale
+    pub fn pitch_classes(self, custom_mask: u16) -> PitchClasses {
+        match self {
+            Scale::Custom => PitchClasses::from_mask(custom_mask),
+            _ => PitchClasses::from_mask(
+                self.degrees()
+                    .iter()
+                    .fold(0, |mask, degree| mask | 1 << degree),
+            ),
+        }
+    }
+}
+
+// degrees of a scale as a 12 bit mask, bit n set when the note n semitones
+// above the root is in the scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchClasses(u16);
+
+impl PitchClasses {
+    pub const CHROMATIC: u16 = 0xfff;
+
+    // the custom scale starts out as the major scale
+    pub const DEFAULT_CUSTOM_MASK: u16 = 0xab5;
+
+    // a scale without any degrees leaves every note as it is, like the chromatic scale
+    pub fn from_mask(mask: u16) -> Self {
+        match mask & Self::CHROMATIC {
+            0 => Self(Self::CHROMATIC),
+            mask => Self(mask),
+        }
+    }
+
+    // semitones above the root of each degree, lowest first
+    pub fn degrees(self) -> impl Iterator<Item = u8> {
+        (0..12).filter(move |&degree| self.0 & 1 << degree != 0)
+    }
+
+    // number of degrees in the scale
+    pub fn num_degrees(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    // whether the note belongs to the scale with the given root pitch class
+    pub fn contains(self, note: u8, root: u8) -> bool {
+        let pitch_class = (note as i32 - root as i32).rem_euclid(12);
+        self.0 & 1 << pitch_class != 0
+    }
+
+    // nearest note in the scale with the given root pitch class,
+    // notes halfway between two degrees snap down
+    pub fn quantize(self, note: u8, root: u8) -> u8 {
+        let pitch_class = (note as i32 - root as i32).rem_euclid(12);
+
+        // look across the octave boundaries too, staying within midi range
+        self.degrees()
+            .flat_map(|degree| [degree as i32 - 12, degree as i32, degree as i32 + 12])
+            .map(|degree| note as i32 + degree - pitch_class)
+            .filter(|quantized| (0..=127).contains(quantized))
+            .min_by_key(|&quantized| ((quantized - note as i32).abs(), quantized > note as i32))
+            .map_or(note, |quantized| quantized as u8)
+    }
+}
@@ -0,0 +1,288 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::midi_clock::ClockMessage;
+use crate::rng::Rng;
+
+// maximum number of notes that can be waiting at once
+const CAPACITY: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteKind {
+    // the voice id tells the notes of different tracks, steps and chord voices apart
+    On { velocity: f32, voice_id: i32 },
+    Off,
+
+    // ends every note sounding on the channel, the note is ignored
+    Release,
+
+    // midi clock for hardware following the internal clock, the channel and note are ignored
+    Clock(ClockMessage),
+
+    // bends every note on the channel, 0.5 leaves them unbent, the note is ignored
+    PitchBend { value: f32 },
+
+    // controller change on the channel, the note is ignored
+    Cc { cc: u8, value: f32 },
+
+    // selects the sound of the channel, the note is ignored
+    ProgramChange { program: u8 },
+
+    // aftertouch of a single sounding note
+    PolyPressure { pressure: f32 },
+
+    // note expressions of a single sounding note, gain 1 and pan and tuning 0 leave it as is
+    Volume { gain: f32 },
+    Pan { pan: f32 },
+    Tuning { tuning: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledNote {
+    pub kind: NoteKind,
+    pub channel: u8,
+    pub note: u8,
+
+    // samples since the scheduler was created, may lie beyond the end of the current buffer
+    pub sample: u64,
+}
+
+// a note waiting in the queue, numbered in the order it was scheduled
+struct Pending {
+    note: ScheduledNote,
+    order: u64,
+}
+
+impl Pending {
+    // note offs sort before note ons at the same sample,
+    // so a repeated note is retriggered instead of cut short,
+    // otherwise notes at the same sample keep the order they were scheduled in
+    fn sort_key(&self) -> (u64, bool, u64) {
+        (
+            self.note.sample,
+            matches!(self.note.kind, NoteKind::On { .. }),
+            self.order,
+        )
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.sort_key() == other.sort_key()
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+// holds note ons and offs until the buffer containing them is processed,
+// notes are kept in absolute sample time so they can wait across any number of buffers
+pub struct NoteScheduler {
+    // earliest note first, the capacity is reserved up front so scheduling never allocates
+    pending: BinaryHeap<Reverse<Pending>>,
+    next_order: u64,
+
+    // absolute sample time of the start of the current buffer
+    buffer_start: u64,
+
+    // tempo the waiting notes were laid out at
+    samples_per_beat: Option<f64>,
+
+    // furthest note ons and offs are moved from where they're scheduled, either way
+    humanize_samples: f64,
+    rng: Rng,
+
+    // notes already due when they were scheduled, since this was last taken
+    late_notes: u64,
+
+    // latest sample a note on and a note off was scheduled at for every note of every
+    // channel, so humanizing never moves a note off before its note on or a retriggered
+    // note on before the note off ending the previous note
+    last_on: [[u64; 128]; 16],
+    last_off: [[u64; 128]; 16],
+}
+
+impl NoteScheduler {
+    // absolute sample time of the given sample index within the current buffer
+    pub fn sample_at(&self, timing: f64) -> u64 {
+        self.buffer_start + timing.max(0.0).round() as u64
+    }
+
+    // returns false if there is no room left for the note
+    pub fn schedule(&mut self, note: ScheduledNote) -> bool {
+        if self.pending.len() >= CAPACITY {
+            return false;
+        }
+
+        let sample = self.humanize(&note);
+        self.push(ScheduledNote { sample, ..note });
+        true
+    }
+
+    // schedule a note at exactly the given sample, whatever the humanize setting,
+    // returns false if there is no room left for the note
+    pub fn schedule_exact(&mut self, note: ScheduledNote) -> bool {
+        if self.pending.len() >= CAPACITY {
+            return false;
+        }
+
+        self.push(note);
+        true
+    }
+
+    fn push(&mut self, note: ScheduledNote) {
+        // notes already due are sent at the start of the current buffer
+        if note.sample < self.buffer_start {
+            self.late_notes += 1;
+        }
+        let note = ScheduledNote {
+            sample: note.sample.max(self.buffer_start),
+            ..note
+        };
+
+        let channel = note.channel as usize & 0x0f;
+        let key = note.note as usize & 0x7f;
+        match note.kind {
+            NoteKind::On { .. } => self.last_on[channel][key] = note.sample,
+            NoteKind::Off => self.last_off[channel][key] = note.sample,
+            _ => {}
+        }
+
+        self.pending.push(Reverse(Pending {
+            note,
+            order: self.next_order,
+        }));
+        self.next_order = self.next_order.wrapping_add(1);
+    }
+
+    // move the notes sent to a sample up to this many samples earlier or later
+    pub fn set_humanize(&mut self, humanize_samples: f64) {
+        self.humanize_samples = humanize_samples.max(0.0);
+    }
+
+    // sample of the note moved by a random amount, only note ons and offs are moved
+    fn humanize(&mut self, note: &ScheduledNote) -> u64 {
+        if self.humanize_samples == 0.0 {
+            return note.sample;
+        }
+
+        let channel = note.channel as usize & 0x0f;
+        let key = note.note as usize & 0x7f;
+        match note.kind {
+            NoteKind::On { .. } | NoteKind::Off => (),
+            // aftertouch and expressions follow their note wherever the note was moved to
+            NoteKind::PolyPressure { .. }
+            | NoteKind::Volume { .. }
+            | NoteKind::Pan { .. }
+            | NoteKind::Tuning { .. } => return note.sample.max(self.last_on[channel][key] + 1),
+            _ => return note.sample,
+        }
+
+        let offset = (self.rng.next_f32() as f64 * 2.0 - 1.0) * self.humanize_samples;
+        let sample = (note.sample as f64 + offset).round().max(0.0) as u64;
+
+        if matches!(note.kind, NoteKind::On { .. }) {
+            sample.max(self.last_off[channel][key])
+        } else {
+            sample.max(self.last_on[channel][key] + 1)
+        }
+    }
+
+    // seed the random timing of the notes scheduled next
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    // removes and returns the earliest note before the given sample index of the current
+    // buffer, along with its sample index
+    pub fn pop_due(&mut self, before: u32) -> Option<(u32, ScheduledNote)> {
+        let end = self.buffer_start + before as u64;
+        if self.pending.peek()?.0.note.sample >= end {
+            return None;
+        }
+
+        let Reverse(pending) = self.pending.pop()?;
+        let timing = (pending.note.sample - self.buffer_start) as u32;
+        Some((timing, pending.note))
+    }
+
+    pub fn take_late_notes(&mut self) -> u64 {
+        std::mem::take(&mut self.late_notes)
+    }
+
+    // move on to the next buffer
+    pub fn advance(&mut self, buffer_samples: u32) {
+        self.buffer_start += buffer_samples as u64;
+    }
+
+    // stretch the time until each waiting note to the new tempo, so notes
+    // scheduled ahead stay at the same place in the song
+    pub fn retime(&mut self, samples_per_beat: f64) {
+        let Some(last_samples_per_beat) = self.samples_per_beat.replace(samples_per_beat) else {
+            return;
+        };
+        if last_samples_per_beat == samples_per_beat || self.pending.is_empty() {
+            return;
+        }
+
+        let ratio = samples_per_beat / last_samples_per_beat;
+
+        // sorting and rebuilding reuse the heap's storage, so this doesn't allocate
+        let mut notes = std::mem::take(&mut self.pending).into_sorted_vec();
+
+        // sorted latest first because of the reverse ordering, walked earliest first
+        let mut previous: Option<(u64, u64)> = None;
+        for Reverse(pending) in notes.iter_mut().rev() {
+            let sample = pending.note.sample;
+            let distance = (sample - self.buffer_start) as f64;
+            let mut retimed = self.buffer_start + (distance * ratio).round() as u64;
+
+            // notes that were apart stay apart, so a note off never moves onto its note on
+            if let Some((previous_sample, previous_retimed)) = previous {
+                if sample == previous_sample {
+                    retimed = previous_retimed;
+                } else {
+                    retimed = retimed.max(previous_retimed + 1);
+                }
+            }
+
+            previous = Some((sample, retimed));
+            pending.note.sample = retimed;
+        }
+
+        self.pending = BinaryHeap::from(notes);
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.last_on = [[0; 128]; 16];
+        self.last_off = [[0; 128]; 16];
+    }
+}
+
+impl Default for NoteScheduler {
+    fn default() -> Self {
+        Self {
+            pending: BinaryHeap::with_capacity(CAPACITY),
+            next_order: 0,
+            buffer_start: 0,
+            samples_per_beat: None,
+            humanize_samples: 0.0,
+            rng: Rng::default(),
+            late_notes: 0,
+            last_on: [[0; 128]; 16],
+            last_off: [[0; 128]; 16],
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// notes sent out that haven't been ended yet, so they can be stopped
+// without sending a note off for every note on every channel
+pub struct SoundingNotes {
+    // one bit per note for each midi channel
+    channels: [u128; 16],
+
+    // voice each note was last started with, kept after the note ends
+    // so note offs already on their way can still be paired with it
+    voice_ids: [[Option<i32>; 128]; 16],
+}
+
+impl SoundingNotes {
+    pub fn note_on(&mut self, channel: u8, note: u8, voice_id: Option<i32>) {
+        self.channels[channel as usize] |= 1 << note;
+        self.voice_ids[channel as usize][note as usize] = voice_id;
+    }
+
+    // voice the note was last started with
+    pub fn voice_id(&self, channel: u8, note: u8) -> Option<i32> {
+        self.voice_ids[channel as usize][note as usize & 0x7f]
+    }
+
+    pub fn contains(&self, channel: u8, note: u8) -> bool {
+        self.channels[channel as usize] & (1 << note) != 0
+    }
+
+    pub fn note_off(&mut self, channel: u8, note: u8) {
+        self.channels[channel as usize] &= !(1 << note);
+    }
+
+    // removes and returns the lowest note sounding on the channel
+    pub fn pop_channel(&mut self, channel: u8) -> Option<u8> {
+        let notes = &mut self.channels[channel as usize];
+        if *notes == 0 {
+            return None;
+        }
+
+        let note = notes.trailing_zeros() as u8;
+        *notes &= !(1 << note);
+        Some(note)
+    }
+
+    // removes and returns a sounding note along with its channel
+    pub fn pop(&mut self) -> Option<(u8, u8)> {
+        (0..16).find_map(|channel| Some((channel, self.pop_channel(channel)?)))
+    }
+}
+
+impl Default for SoundingNotes {
+    fn default() -> Self {
+        Self {
+            channels: [0; 16],
+            voice_ids: [[None; 128]; 16],
+        }
+    }
+}
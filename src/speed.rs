@@ -0,0 +1,28 @@
+use nih_plug::prelude::*;
+
+// playback speed relative to the host's tempo, every track's steps are scaled by it
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    #[id = "half"]
+    #[name = "×0.5"]
+    Half,
+
+    #[id = "normal"]
+    #[name = "×1"]
+    Normal,
+
+    #[id = "double"]
+    #[name = "×2"]
+    Double,
+}
+
+impl Speed {
+    // multiplier applied to the length of a step
+    pub fn step_factor(self) -> f64 {
+        match self {
+            Self::Half => 2.0,
+            Self::Normal => 1.0,
+            Self::Double => 0.5,
+        }
+    }
+}
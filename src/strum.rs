@@ -0,0 +1,52 @@
+use nih_plug::prelude::*;
+
+use crate::pattern::MAX_CHORD_NOTES;
+
+// order the notes of a strummed chord start in
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum StrumDirection {
+    // lowest note first
+    #[id = "up"]
+    #[name = "Up"]
+    Up,
+
+    #[id = "down"]
+    #[name = "Down"]
+    Down,
+
+    // up and down strokes take turns, like strumming a guitar
+    #[id = "alternate"]
+    #[name = "Alternate"]
+    Alternate,
+}
+
+impl StrumDirection {
+    // delay of each of the notes in samples, with the given gap between one note
+    // and the next. stroke counts the strums, alternate strums up on even strokes.
+    pub fn delays(
+        self,
+        notes: &[Option<u8>; MAX_CHORD_NOTES],
+        gap_samples: f64,
+        stroke: i64,
+    ) -> [u64; MAX_CHORD_NOTES] {
+        let up = match self {
+            Self::Up => true,
+            Self::Down => false,
+            Self::Alternate => stroke.rem_euclid(2) == 0,
+        };
+        let num_notes = notes.iter().flatten().count();
+
+        std::array::from_fn(|index| {
+            let Some(note) = notes[index] else {
+                return 0;
+            };
+            let lower = notes
+                .iter()
+                .flatten()
+                .filter(|&&other| other < note)
+                .count();
+            let position = if up { lower } else { num_notes - 1 - lower };
+            (position as f64 * gap_samples).round() as u64
+        })
+    }
+}
@@ -0,0 +1,43 @@
+// swing delays every even numbered step (odd step count) by a fraction of a step,
+// lengthening the step before it and shortening the delayed step by the same amount.
+// a swung position is measured in steps such that every step starts on a whole number.
+
+fn is_delayed(step_count: i64) -> bool {
+    step_count.rem_euclid(2) == 1
+}
+
+// length of the given step in straight steps
+pub fn step_len(step_count: i64, delay: f64) -> f64 {
+    if is_delayed(step_count) {
+        1.0 - delay
+    } else {
+        1.0 + delay
+    }
+}
+
+// converts a position in straight steps to a swung position
+pub fn swung_pos(pos_steps: f64, delay: f64) -> f64 {
+    let floor = pos_steps.floor();
+    let frac = pos_steps - floor;
+
+    if !is_delayed(floor as i64) {
+        floor + frac / step_len(0, delay)
+    } else if frac < delay {
+        // still inside the lengthened step before the delayed one
+        floor - 1.0 + (1.0 + frac) / step_len(0, delay)
+    } else {
+        floor + (frac - delay) / step_len(1, delay)
+    }
+}
+
+// converts a swung position to a position in straight steps
+pub fn straight_pos(swung_pos: f64, delay: f64) -> f64 {
+    let floor = swung_pos.floor();
+    let frac = swung_pos - floor;
+
+    if is_delayed(floor as i64) {
+        floor + delay + frac * step_len(1, delay)
+    } else {
+        floor + frac * step_len(0, delay)
+    }
+}
@@ -0,0 +1,198 @@
+use nih_plug::prelude::{Enum, SysExMessage};
+
+use crate::midi_clock::ClockMessage;
+use crate::pattern::{Pattern, Step};
+
+const START: u8 = 0xf0;
+const END: u8 = 0xf7;
+
+// manufacturer id set aside for non-commercial use
+const MANUFACTURER_ID: u8 = 0x7d;
+
+// tells the sequencer's messages apart from other devices using the same manufacturer id
+const MODEL_ID: u8 = 0x4e;
+
+const TRACK_HEADER: u8 = 0x01;
+const STEP_DATA: u8 = 0x02;
+
+// bytes of a step after its pattern, track and step number
+pub const STEP_DATA_LEN: usize = 7;
+
+// bytes of a step message ahead of its data
+const STEP_HEADER_LEN: usize = 7;
+
+// longest message, a step with its end byte
+const MAX_LEN: usize = STEP_HEADER_LEN + STEP_DATA_LEN + 1;
+
+// flag bits of a step
+const ENABLED: u8 = 0x01;
+const ACCENT: u8 = 0x02;
+const TIE: u8 = 0x04;
+const SLIDE: u8 = 0x08;
+
+// every sysex message the plugin sends or understands. a pattern dump is a header for
+// each track followed by one message per step, every data byte kept below 0x80:
+//
+//   f0 7d 4e 01 <pattern> <track> <len> <rate> f7
+//   f0 7d 4e 02 <pattern> <track> <step> <flags> <note> <velocity> <gate>
+//               <probability> <ratchets> <offset> f7
+//
+// velocity, gate and probability are scaled to 0-127, the offset to 0-127 with 64
+// on the grid, flags hold on, accent, tie and slide in their lowest four bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SysEx {
+    Clock(ClockMessage),
+
+    // length and rate of a track of the dumped pattern
+    TrackHeader {
+        pattern: u8,
+        track: u8,
+        len: u8,
+        rate: u8,
+    },
+
+    // one step of a track of the dumped pattern
+    Step {
+        pattern: u8,
+        track: u8,
+        step: u8,
+        data: [u8; STEP_DATA_LEN],
+    },
+}
+
+impl SysExMessage for SysEx {
+    type Buffer = [u8; MAX_LEN];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        match *buffer {
+            [START, MANUFACTURER_ID, MODEL_ID, TRACK_HEADER, pattern, track, len, rate, END] => {
+                Some(Self::TrackHeader {
+                    pattern,
+                    track,
+                    len,
+                    rate,
+                })
+            }
+            [START, MANUFACTURER_ID, MODEL_ID, STEP_DATA, pattern, track, step, ref data @ .., END]
+                if data.len() == STEP_DATA_LEN =>
+            {
+                Some(Self::Step {
+                    pattern,
+                    track,
+                    step,
+                    data: data.try_into().ok()?,
+                })
+            }
+            _ => ClockMessage::from_buffer(buffer).map(Self::Clock),
+        }
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        let mut buffer = [0; MAX_LEN];
+        let len = match self {
+            Self::Clock(message) => {
+                let (bytes, len) = message.to_buffer();
+                buffer[..len].copy_from_slice(&bytes[..len]);
+                len
+            }
+            Self::TrackHeader {
+                pattern,
+                track,
+                len,
+                rate,
+            } => {
+                let message = [
+                    START,
+                    MANUFACTURER_ID,
+                    MODEL_ID,
+                    TRACK_HEADER,
+                    pattern,
+                    track,
+                    len,
+                    rate,
+                    END,
+                ];
+                buffer[..message.len()].copy_from_slice(&message);
+                message.len()
+            }
+            Self::Step {
+                pattern,
+                track,
+                step,
+                data,
+            } => {
+                buffer[..STEP_HEADER_LEN].copy_from_slice(&[
+                    START,
+                    MANUFACTURER_ID,
+                    MODEL_ID,
+                    STEP_DATA,
+                    pattern,
+                    track,
+                    step,
+                ]);
+                buffer[STEP_HEADER_LEN..MAX_LEN - 1].copy_from_slice(&data);
+                buffer[MAX_LEN - 1] = END;
+                MAX_LEN
+            }
+        };
+        (buffer, len)
+    }
+}
+
+// the messages of a dump of the pattern in the given slot, only the steps
+// within each track's length are sent
+pub fn dump(pattern: &Pattern, index: usize) -> impl Iterator<Item = SysEx> + '_ {
+    let index = index as u8 & 0x7f;
+    pattern
+        .tracks
+        .iter()
+        .enumerate()
+        .flat_map(move |(track_index, track)| {
+            let track_index = track_index as u8 & 0x7f;
+            let header = SysEx::TrackHeader {
+                pattern: index,
+                track: track_index,
+                len: track.len as u8 & 0x7f,
+                rate: track.rate.to_index() as u8 & 0x7f,
+            };
+            let steps =
+                track.steps[..track.len]
+                    .iter()
+                    .enumerate()
+                    .map(move |(step_index, step)| SysEx::Step {
+                        pattern: index,
+                        track: track_index,
+                        step: step_index as u8 & 0x7f,
+                        data: step_data(step),
+                    });
+            std::iter::once(header).chain(steps)
+        })
+}
+
+fn step_data(step: &Step) -> [u8; STEP_DATA_LEN] {
+    let flags = [
+        (step.enabled, ENABLED),
+        (step.accent, ACCENT),
+        (step.tie, TIE),
+        (step.slide, SLIDE),
+    ]
+    .into_iter()
+    .filter(|&(set, _)| set)
+    .fold(0, |flags, (_, bit)| flags | bit);
+    let offset = 0.5 + step.offset / Step::MAX_OFFSET / 2.0;
+
+    [
+        flags,
+        step.note & 0x7f,
+        seven_bit(step.velocity),
+        seven_bit(step.gate),
+        seven_bit(step.probability),
+        step.ratchets & 0x7f,
+        seven_bit(offset),
+    ]
+}
+
+// a value between 0 and 1 as a data byte
+fn seven_bit(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 127.0).round() as u8
+}
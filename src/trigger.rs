@@ -0,0 +1,19 @@
+// turns a bool parameter into a one shot action that fires when the
+// parameter is switched on, so undoing the switch in the host doesn't fire it again
+#[derive(Default)]
+pub struct Trigger {
+    last_value: bool,
+}
+
+impl Trigger {
+    pub fn fired(&mut self, value: bool) -> bool {
+        let fired = value && !self.last_value;
+        self.last_value = value;
+        fired
+    }
+
+    // take on the current value without firing, e.g. after state is restored
+    pub fn sync(&mut self, value: bool) {
+        self.last_value = value;
+    }
+}
@@ -0,0 +1,27 @@
+use nih_plug::prelude::*;
+
+use crate::pattern::Step;
+
+// how a step is played when its variation chance comes up, instead of as programmed
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Variation {
+    #[id = "staccato"]
+    #[name = "Staccato"]
+    Staccato,
+
+    #[id = "soft"]
+    #[name = "Half Velocity"]
+    Soft,
+}
+
+impl Variation {
+    // gate of a staccato step, unless it's already shorter
+    const STACCATO_GATE: f32 = 0.2;
+
+    pub fn apply(self, step: &mut Step) {
+        match self {
+            Self::Staccato => step.gate = step.gate.min(Self::STACCATO_GATE),
+            Self::Soft => step.velocity *= 0.5,
+        }
+    }
+}
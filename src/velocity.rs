@@ -0,0 +1,30 @@
+use nih_plug::prelude::*;
+
+// response applied to every velocity sent out, to suit the instrument played
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum VelocityCurve {
+    #[id = "linear"]
+    #[name = "Linear"]
+    Linear,
+
+    // soft velocities get softer, for instruments that respond too strongly
+    #[id = "exponential"]
+    #[name = "Exponential"]
+    Exponential,
+
+    // the middle of the range is spread out and the ends squeezed together
+    #[id = "s_curve"]
+    #[name = "S-Curve"]
+    SCurve,
+}
+
+impl VelocityCurve {
+    pub fn apply(self, velocity: f32) -> f32 {
+        let velocity = velocity.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => velocity,
+            Self::Exponential => velocity * velocity,
+            Self::SCurve => velocity * velocity * (3.0 - 2.0 * velocity),
+        }
+    }
+}